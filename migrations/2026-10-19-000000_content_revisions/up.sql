@@ -0,0 +1,11 @@
+CREATE TABLE content_revisions(
+    id INTEGER PRIMARY KEY NOT NULL,
+    content_id VARCHAR(255) NOT NULL,
+    title VARCHAR(1000) NOT NULL,
+    body TEXT NOT NULL,
+    diff TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY(content_id) REFERENCES contents(id)
+);
+
+CREATE INDEX content_revisions_created_at ON content_revisions(created_at);
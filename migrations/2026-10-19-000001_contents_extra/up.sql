@@ -0,0 +1 @@
+ALTER TABLE contents ADD COLUMN extra TEXT NOT NULL DEFAULT '{}';
@@ -0,0 +1 @@
+ALTER TABLE contents ADD COLUMN published_at TIMESTAMP;
@@ -0,0 +1 @@
+ALTER TABLE sources ADD COLUMN crawl_interval INTEGER NOT NULL DEFAULT 3600;
@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS sources_next_run;
+ALTER TABLE sources DROP COLUMN next_run;
+ALTER TABLE sources DROP COLUMN enabled;
+ALTER TABLE sources DROP COLUMN timezone;
+ALTER TABLE sources DROP COLUMN cron;
@@ -0,0 +1,9 @@
+-- cronがNULLのsourceはcrawl_interval秒ごとにcrawlする
+ALTER TABLE sources ADD COLUMN cron TEXT;
+ALTER TABLE sources ADD COLUMN timezone TEXT NOT NULL DEFAULT 'UTC';
+ALTER TABLE sources ADD COLUMN enabled BOOLEAN NOT NULL DEFAULT 1;
+ALTER TABLE sources ADD COLUMN next_run TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00';
+
+UPDATE sources SET next_run = datetime(last_accessed, '+' || crawl_interval || ' seconds');
+
+CREATE INDEX sources_next_run ON sources(next_run);
@@ -0,0 +1,13 @@
+CREATE TABLE job_runs(
+    id INTEGER PRIMARY KEY NOT NULL,
+    source_id INTEGER NOT NULL,
+    started_at TIMESTAMP NOT NULL,
+    finished_at TIMESTAMP NOT NULL,
+    status VARCHAR(255) NOT NULL,
+    pages_fetched INTEGER NOT NULL,
+    contents_inserted INTEGER NOT NULL,
+    error TEXT,
+    FOREIGN KEY(source_id) REFERENCES sources(id)
+);
+
+CREATE INDEX job_runs_source_id_started_at ON job_runs(source_id, started_at);
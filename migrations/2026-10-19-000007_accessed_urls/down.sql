@@ -0,0 +1,6 @@
+ALTER TABLE sources ADD COLUMN last_accessed_urls TEXT NOT NULL DEFAULT '';
+UPDATE sources SET last_accessed_urls = COALESCE(
+    (SELECT group_concat(url, ',') FROM accessed_urls WHERE accessed_urls.source_id = sources.id),
+    ''
+);
+DROP TABLE accessed_urls;
@@ -0,0 +1,21 @@
+-- crawlしたurlをsourceごとに1行ずつ保存する。次のcrawlではここにあるurlをskipする
+CREATE TABLE accessed_urls(
+    source_id INTEGER NOT NULL,
+    url TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    last_accessed TIMESTAMP NOT NULL,
+    PRIMARY KEY(source_id, url),
+    FOREIGN KEY(source_id) REFERENCES sources(id)
+);
+
+-- カンマ区切りのlast_accessed_urlsを1行ずつに分ける
+INSERT OR IGNORE INTO accessed_urls(source_id, url, created_at, last_accessed)
+WITH RECURSIVE split(source_id, url, rest, accessed) AS (
+    SELECT id, '', last_accessed_urls || ',', last_accessed FROM sources
+    UNION ALL
+    SELECT source_id, substr(rest, 1, instr(rest, ',') - 1), substr(rest, instr(rest, ',') + 1), accessed
+    FROM split WHERE rest <> ''
+)
+SELECT source_id, url, accessed, accessed FROM split WHERE url <> '';
+
+ALTER TABLE sources DROP COLUMN last_accessed_urls;
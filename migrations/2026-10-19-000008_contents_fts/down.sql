@@ -0,0 +1 @@
+DROP TABLE contents_fts;
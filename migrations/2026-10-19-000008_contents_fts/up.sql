@@ -0,0 +1,10 @@
+-- contentsのtitle, bodyの全文検索用の索引。commit_job_resultで更新する
+-- 日本語のように空白で区切らない文も探せるようにtrigramで分割する
+CREATE VIRTUAL TABLE contents_fts USING fts5(
+    content_id UNINDEXED,
+    title,
+    body,
+    tokenize = 'trigram'
+);
+
+INSERT INTO contents_fts(content_id, title, body) SELECT id, title, body FROM contents;
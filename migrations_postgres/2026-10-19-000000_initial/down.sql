@@ -0,0 +1,4 @@
+DROP TABLE IF EXISTS job_runs;
+DROP TABLE IF EXISTS content_revisions;
+DROP TABLE IF EXISTS contents;
+DROP TABLE IF EXISTS sources;
@@ -0,0 +1,56 @@
+-- migrations/ と同じtableをPostgreSQLの型で作る
+CREATE TABLE sources(
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    url TEXT NOT NULL,
+    selectors JSONB NOT NULL,
+    last_accessed TIMESTAMPTZ NOT NULL,
+    last_accessed_urls TEXT[] NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    -- cronがNULLのsourceはcrawl_interval秒ごとにcrawlする
+    crawl_interval INTEGER NOT NULL DEFAULT 3600,
+    cron TEXT,
+    timezone TEXT NOT NULL DEFAULT 'UTC',
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    next_run TIMESTAMPTZ NOT NULL DEFAULT 'epoch',
+    consecutive_failures INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE INDEX sources_next_run ON sources(next_run);
+
+CREATE TABLE contents(
+    id TEXT PRIMARY KEY,
+    url TEXT NOT NULL,
+    source_id INTEGER NOT NULL REFERENCES sources(id),
+    title TEXT NOT NULL,
+    body TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    extra JSONB NOT NULL DEFAULT '{}',
+    published_at TIMESTAMPTZ
+);
+
+CREATE INDEX contents_source_id_url ON contents(source_id, url);
+
+CREATE TABLE content_revisions(
+    id SERIAL PRIMARY KEY,
+    content_id TEXT NOT NULL REFERENCES contents(id),
+    title TEXT NOT NULL,
+    body TEXT NOT NULL,
+    diff TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX content_revisions_created_at ON content_revisions(created_at);
+
+CREATE TABLE job_runs(
+    id SERIAL PRIMARY KEY,
+    source_id INTEGER NOT NULL REFERENCES sources(id),
+    started_at TIMESTAMPTZ NOT NULL,
+    finished_at TIMESTAMPTZ NOT NULL,
+    status TEXT NOT NULL,
+    pages_fetched INTEGER NOT NULL,
+    contents_inserted INTEGER NOT NULL,
+    error TEXT
+);
+
+CREATE INDEX job_runs_source_id_started_at ON job_runs(source_id, started_at);
@@ -0,0 +1,15 @@
+-- crawlしたurlをsourceごとに1行ずつ保存する。次のcrawlではここにあるurlをskipする
+CREATE TABLE accessed_urls(
+    source_id INTEGER NOT NULL REFERENCES sources(id),
+    url TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_accessed TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY(source_id, url)
+);
+
+INSERT INTO accessed_urls(source_id, url, created_at, last_accessed)
+SELECT DISTINCT sources.id, accessed.url, sources.last_accessed, sources.last_accessed
+FROM sources, unnest(sources.last_accessed_urls) AS accessed(url)
+WHERE accessed.url <> '';
+
+ALTER TABLE sources DROP COLUMN last_accessed_urls;
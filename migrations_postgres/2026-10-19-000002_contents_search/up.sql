@@ -0,0 +1,3 @@
+-- contentsのtitle, bodyの全文検索用の索引。searchで同じ式を使う
+CREATE INDEX contents_search ON contents
+USING GIN (to_tsvector('simple', title || ' ' || body));
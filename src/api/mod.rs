@@ -0,0 +1,491 @@
+#[cfg(test)]
+mod test;
+
+use crate::crawler::SelectorTree;
+use crate::dispatcher::{
+    search_terms, ContentCursor, ContentFilter, DataStore, Dispatcher, Schedule,
+};
+use crate::entity::SearchHit;
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use warp::http::StatusCode;
+use warp::hyper::body::Bytes;
+use warp::reply::{self, Reply};
+use warp::{Filter, Rejection};
+
+// GET    /sources                 list sources
+// POST   /sources                 add a source from a sitemap
+// GET    /sources/:id             show a source
+// PUT    /sources/:id             replace the sitemap of a source
+// DELETE /sources/:id             remove a source with its contents
+// PUT    /sources/:id/schedule    change the schedule of a source
+// POST   /sources/:id/enable      enable a source and crawl it on the next poll
+// POST   /sources/:id/disable     disable a source
+// POST   /sources/:id/crawl       crawl a source now, and returns the job run
+// GET    /sources/:id/runs?limit= list the latest job runs of a source
+// GET    /contents?source_id=&since=&until=&q=&cursor=&limit=
+//                                 list contents from the newest. since and until are RFC 3339,
+//                                 and q is a keyword in the title or the body
+// GET    /search?q=&source_id=&since=&until=&offset=&limit=
+//                                 search contents by relevance. q is words of 3 or more
+//                                 characters separated by spaces, and every word must occur
+// limit is 20 by default, and clamped to 1..=100
+
+// sitemaps and schedules are small JSON documents
+const MAX_BODY_BYTES: u64 = 1024 * 1024;
+
+pub fn routes<D>(
+    dispatcher: Arc<Dispatcher<D>>,
+) -> impl Filter<Extract = impl Reply, Error = Infallible> + Clone
+where
+    D: DataStore + Send + Sync + 'static,
+{
+    let dispatcher = warp::any().map(move || Arc::clone(&dispatcher));
+
+    let list_sources = warp::path!("sources")
+        .and(warp::get())
+        .and(dispatcher.clone())
+        .and_then(list_sources);
+    let add_source = warp::path!("sources")
+        .and(warp::post())
+        .and(warp::body::content_length_limit(MAX_BODY_BYTES))
+        .and(warp::body::bytes())
+        .and(dispatcher.clone())
+        .and_then(add_source);
+    let show_source = warp::path!("sources" / i32)
+        .and(warp::get())
+        .and(dispatcher.clone())
+        .and_then(show_source);
+    let update_source = warp::path!("sources" / i32)
+        .and(warp::put())
+        .and(warp::body::content_length_limit(MAX_BODY_BYTES))
+        .and(warp::body::bytes())
+        .and(dispatcher.clone())
+        .and_then(update_source);
+    let set_schedule = warp::path!("sources" / i32 / "schedule")
+        .and(warp::put())
+        .and(warp::body::content_length_limit(MAX_BODY_BYTES))
+        .and(warp::body::json())
+        .and(dispatcher.clone())
+        .and_then(set_schedule);
+    let enable_source = warp::path!("sources" / i32 / "enable")
+        .and(warp::post())
+        .and(dispatcher.clone())
+        .and_then(|id, dispatcher| set_enabled(id, true, dispatcher));
+    let disable_source = warp::path!("sources" / i32 / "disable")
+        .and(warp::post())
+        .and(dispatcher.clone())
+        .and_then(|id, dispatcher| set_enabled(id, false, dispatcher));
+    let remove_source = warp::path!("sources" / i32)
+        .and(warp::delete())
+        .and(dispatcher.clone())
+        .and_then(remove_source);
+    let crawl_source = warp::path!("sources" / i32 / "crawl")
+        .and(warp::post())
+        .and(dispatcher.clone())
+        .and_then(crawl_source);
+    let list_job_runs = warp::path!("sources" / i32 / "runs")
+        .and(warp::get())
+        .and(warp::query::<RunsQuery>())
+        .and(dispatcher.clone())
+        .and_then(list_job_runs);
+    let list_contents = warp::path!("contents")
+        .and(warp::get())
+        .and(warp::query::<ContentsQuery>())
+        .and(dispatcher.clone())
+        .and_then(list_contents);
+    let search_contents = warp::path!("search")
+        .and(warp::get())
+        .and(warp::query::<SearchQuery>())
+        .and(dispatcher)
+        .and_then(search_contents);
+
+    list_sources
+        .or(add_source)
+        .or(show_source)
+        .or(update_source)
+        .or(set_schedule)
+        .or(enable_source)
+        .or(disable_source)
+        .or(remove_source)
+        .or(crawl_source)
+        .or(list_job_runs)
+        .or(list_contents)
+        .or(search_contents)
+        .recover(recover)
+}
+
+// binds addr, and returns the server. it stops accepting connections when shutdown completes,
+// and finishes after in-flight requests
+pub fn serve<D, F>(
+    dispatcher: Arc<Dispatcher<D>>,
+    addr: SocketAddr,
+    shutdown: F,
+) -> anyhow::Result<(SocketAddr, impl Future<Output = ()>)>
+where
+    D: DataStore + Send + Sync + 'static,
+    F: Future<Output = ()> + Send + 'static,
+{
+    warp::serve(routes(dispatcher))
+        .try_bind_with_graceful_shutdown(addr, shutdown)
+        // the error of warp repeats the message of its source
+        .map_err(|err| anyhow!("failed to listen on {}: {}", addr, err))
+}
+
+#[derive(Deserialize, Debug)]
+struct ContentsQuery {
+    source_id: Option<i32>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    q: Option<String>,
+    // next_cursor of the previous page
+    cursor: Option<String>,
+    #[serde(default = "default_limit")]
+    limit: i64,
+}
+
+#[derive(Deserialize, Debug)]
+struct SearchQuery {
+    q: String,
+    source_id: Option<i32>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    // next_offset of the previous page
+    #[serde(default)]
+    offset: i64,
+    #[serde(default = "default_limit")]
+    limit: i64,
+}
+
+fn default_limit() -> i64 {
+    20
+}
+
+// a page has at least one item, and at most MAX_LIMIT
+const MAX_LIMIT: i64 = 100;
+
+fn clamp_limit(limit: i64) -> i64 {
+    limit.clamp(1, MAX_LIMIT)
+}
+
+#[derive(Deserialize, Debug)]
+struct RunsQuery {
+    #[serde(default = "default_limit")]
+    limit: i64,
+}
+
+// ex: {"schedule": {"type": "cron", "expression": "0 0 9 * * *", "timezone": "Asia/Tokyo"}}
+#[derive(Deserialize, Debug)]
+struct ScheduleBody {
+    schedule: Schedule,
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Serialize, Debug)]
+struct Page<T> {
+    items: Vec<T>,
+    // cursor of the next page, None on the last page
+    next_cursor: Option<String>,
+    // number of the items on all pages
+    total: i64,
+}
+
+#[derive(Serialize, Debug)]
+struct SearchPage {
+    items: Vec<SearchHit>,
+    // offset of the next page, None on the last page
+    next_offset: Option<i64>,
+}
+
+#[derive(Debug)]
+enum ApiError {
+    BadRequest(anyhow::Error),
+    NotFound,
+    Internal(anyhow::Error),
+}
+
+impl warp::reject::Reject for ApiError {}
+
+fn internal(err: anyhow::Error) -> Rejection {
+    warp::reject::custom(ApiError::Internal(err))
+}
+
+fn not_found() -> Rejection {
+    warp::reject::custom(ApiError::NotFound)
+}
+
+fn parse_sitemap(body: Bytes) -> Result<SelectorTree, Rejection> {
+    String::from_utf8(body.to_vec())
+        .map_err(|err| anyhow!(err))
+        .and_then(SelectorTree::new)
+        .and_then(|selector_tree| selector_tree.validate().map(|_| selector_tree))
+        .map_err(|err| warp::reject::custom(ApiError::BadRequest(err)))
+}
+
+async fn list_sources<D: DataStore>(
+    dispatcher: Arc<Dispatcher<D>>,
+) -> Result<impl Reply, Rejection> {
+    let sources = dispatcher
+        .data_store()
+        .get_sources()
+        .await
+        .map_err(internal)?;
+    Ok(reply::json(&sources))
+}
+
+async fn add_source<D: DataStore>(
+    body: Bytes,
+    dispatcher: Arc<Dispatcher<D>>,
+) -> Result<impl Reply, Rejection> {
+    let selector_tree = parse_sitemap(body)?;
+    let id = dispatcher
+        .data_store()
+        .add_source(selector_tree)
+        .await
+        .map_err(internal)?;
+    let source = dispatcher
+        .data_store()
+        .get_source(id)
+        .await
+        .map_err(internal)?
+        .ok_or_else(not_found)?;
+    Ok(reply::with_status(
+        reply::json(&source),
+        StatusCode::CREATED,
+    ))
+}
+
+async fn show_source<D: DataStore>(
+    id: i32,
+    dispatcher: Arc<Dispatcher<D>>,
+) -> Result<impl Reply, Rejection> {
+    let source = dispatcher
+        .data_store()
+        .get_source(id)
+        .await
+        .map_err(internal)?
+        .ok_or_else(not_found)?;
+    Ok(reply::json(&source))
+}
+
+async fn update_source<D: DataStore>(
+    id: i32,
+    body: Bytes,
+    dispatcher: Arc<Dispatcher<D>>,
+) -> Result<impl Reply, Rejection> {
+    let selector_tree = parse_sitemap(body)?;
+    if !dispatcher
+        .data_store()
+        .update_source(id, selector_tree)
+        .await
+        .map_err(internal)?
+    {
+        return Err(not_found());
+    }
+    show_source(id, dispatcher).await
+}
+
+async fn set_schedule<D: DataStore>(
+    id: i32,
+    body: ScheduleBody,
+    dispatcher: Arc<Dispatcher<D>>,
+) -> Result<impl Reply, Rejection> {
+    // reject expressions which can not be scheduled before storing them
+    body.schedule
+        .next_after(Utc::now())
+        .map_err(|err| warp::reject::custom(ApiError::BadRequest(err)))?;
+    if !dispatcher
+        .data_store()
+        .set_schedule(id, body.schedule, body.enabled)
+        .await
+        .map_err(internal)?
+    {
+        return Err(not_found());
+    }
+    show_source(id, dispatcher).await
+}
+
+async fn set_enabled<D: DataStore>(
+    id: i32,
+    enabled: bool,
+    dispatcher: Arc<Dispatcher<D>>,
+) -> Result<impl Reply, Rejection> {
+    if !dispatcher
+        .data_store()
+        .set_enabled(id, enabled)
+        .await
+        .map_err(internal)?
+    {
+        return Err(not_found());
+    }
+    show_source(id, dispatcher).await
+}
+
+async fn remove_source<D: DataStore>(
+    id: i32,
+    dispatcher: Arc<Dispatcher<D>>,
+) -> Result<impl Reply, Rejection> {
+    if !dispatcher
+        .data_store()
+        .remove_source(id)
+        .await
+        .map_err(internal)?
+    {
+        return Err(not_found());
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn crawl_source<D: DataStore>(
+    id: i32,
+    dispatcher: Arc<Dispatcher<D>>,
+) -> Result<impl Reply, Rejection> {
+    let source = dispatcher
+        .data_store()
+        .get_source(id)
+        .await
+        .map_err(internal)?
+        .ok_or_else(not_found)?;
+    let job_run = dispatcher.run(source).await.map_err(internal)?;
+    Ok(reply::json(&job_run))
+}
+
+async fn list_job_runs<D: DataStore>(
+    id: i32,
+    query: RunsQuery,
+    dispatcher: Arc<Dispatcher<D>>,
+) -> Result<impl Reply, Rejection> {
+    let job_runs = dispatcher
+        .data_store()
+        .get_job_runs(id, clamp_limit(query.limit))
+        .await
+        .map_err(internal)?;
+    Ok(reply::json(&job_runs))
+}
+
+async fn list_contents<D: DataStore>(
+    query: ContentsQuery,
+    dispatcher: Arc<Dispatcher<D>>,
+) -> Result<impl Reply, Rejection> {
+    let cursor = query
+        .cursor
+        .map(|cursor| cursor.parse::<ContentCursor>())
+        .transpose()
+        .map_err(|err| warp::reject::custom(ApiError::BadRequest(err)))?;
+    let filter = ContentFilter {
+        source_id: query.source_id,
+        since: query.since,
+        until: query.until,
+        // an empty q matches everything
+        keyword: query.q.filter(|q| !q.is_empty()),
+    };
+
+    let data_store = dispatcher.data_store();
+    let page = data_store
+        .query_contents(filter.clone(), cursor, clamp_limit(query.limit))
+        .await
+        .map_err(internal)?;
+    let total = data_store.count_contents(filter).await.map_err(internal)?;
+    Ok(reply::json(&Page {
+        items: page.items,
+        next_cursor: page.next_cursor.map(|cursor| cursor.to_string()),
+        total,
+    }))
+}
+
+async fn search_contents<D: DataStore>(
+    query: SearchQuery,
+    dispatcher: Arc<Dispatcher<D>>,
+) -> Result<impl Reply, Rejection> {
+    // a malformed query is the client's fault, while the data store fails only on its own errors
+    search_terms(&query.q).map_err(|err| warp::reject::custom(ApiError::BadRequest(err)))?;
+    let filter = ContentFilter {
+        source_id: query.source_id,
+        since: query.since,
+        until: query.until,
+        keyword: None,
+    };
+    let limit = clamp_limit(query.limit);
+    let offset = query.offset.max(0);
+
+    // one more hit tells whether the next page exists
+    let mut items = dispatcher
+        .data_store()
+        .search(query.q, filter, limit + 1, offset)
+        .await
+        .map_err(internal)?;
+    let next_offset = if items.len() as i64 > limit {
+        items.truncate(limit as usize);
+        Some(offset + limit)
+    } else {
+        None
+    };
+    Ok(reply::json(&SearchPage { items, next_offset }))
+}
+
+async fn recover(rejection: Rejection) -> Result<impl Reply, Infallible> {
+    let (status, message) = if rejection.is_not_found() {
+        (StatusCode::NOT_FOUND, "not found".to_string())
+    } else if let Some(err) = rejection.find::<ApiError>() {
+        match err {
+            ApiError::BadRequest(err) => (StatusCode::BAD_REQUEST, err.to_string()),
+            ApiError::NotFound => (StatusCode::NOT_FOUND, "not found".to_string()),
+            ApiError::Internal(err) => {
+                // the details stay in the log, not in the response
+                log::error!("api: {:?}", err);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "internal error".to_string(),
+                )
+            }
+        }
+    } else if rejection.find::<warp::reject::PayloadTooLarge>().is_some() {
+        (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "payload too large".to_string(),
+        )
+    } else if rejection.find::<warp::reject::LengthRequired>().is_some() {
+        (StatusCode::LENGTH_REQUIRED, "length required".to_string())
+    } else if rejection
+        .find::<warp::reject::UnsupportedMediaType>()
+        .is_some()
+    {
+        (
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "unsupported media type".to_string(),
+        )
+    } else if let Some(err) = rejection.find::<warp::body::BodyDeserializeError>() {
+        (StatusCode::BAD_REQUEST, err.to_string())
+    } else if rejection.find::<warp::reject::InvalidQuery>().is_some() {
+        (StatusCode::BAD_REQUEST, "invalid query".to_string())
+    // the other routes of the same path reject with 405, so it is looked for last
+    } else if rejection.find::<warp::reject::MethodNotAllowed>().is_some() {
+        (
+            StatusCode::METHOD_NOT_ALLOWED,
+            "method not allowed".to_string(),
+        )
+    } else {
+        log::error!("api: unhandled rejection {:?}", rejection);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal error".to_string(),
+        )
+    };
+
+    Ok(reply::with_status(
+        reply::json(&json!({ "error": message })),
+        status,
+    ))
+}
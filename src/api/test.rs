@@ -0,0 +1,398 @@
+use crate::api::{routes, serve};
+use crate::crawler::SelectorTree;
+use crate::dispatcher::{DataStore, DataStoreAdapter, Dispatcher, MemoryDataStore};
+use crate::entity::{Content, JobRun, JobStatus};
+use chrono::{TimeZone, Utc};
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::sqlite::SqliteConnection;
+use serde_json::{Map, Value};
+use std::sync::Arc;
+use warp::http::StatusCode;
+
+fn fake_contents(source_id: i32) -> Vec<Content> {
+    (0..3)
+        .map(|i| Content {
+            id: format!("{}-{}", source_id, i),
+            url: format!("https://formula1-data.com/article/{}", i),
+            source_id,
+            title: format!("title {}", i),
+            body: format!("body {}", i),
+            extra: Map::new(),
+            published_at: None,
+            created_at: Utc.with_ymd_and_hms(2020, 8, 1, 0, 0, 0).unwrap(),
+        })
+        .collect()
+}
+
+fn sitemap(id: &str) -> String {
+    format!(
+        r#"{{"_id": "{}", "startUrl": ["https://formula1-data.com/article"], "selectors": []}}"#,
+        id
+    )
+}
+
+fn body(response: &warp::http::Response<warp::hyper::body::Bytes>) -> Value {
+    serde_json::from_slice(response.body()).unwrap()
+}
+
+#[tokio::test]
+async fn sources_test() {
+    let api = routes(Arc::new(Dispatcher::new(MemoryDataStore::new())));
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/sources")
+        .body(sitemap("formula1-data"))
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(body(&response)["id"], 1);
+    assert_eq!(body(&response)["name"], "formula1-data");
+
+    let response = warp::test::request()
+        .method("PUT")
+        .path("/sources/1")
+        .body(sitemap("formula1-data-2"))
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    // the name is kept, and the sitemap is replaced
+    assert_eq!(body(&response)["name"], "formula1-data");
+    assert_eq!(body(&response)["selectors"]["_id"], "formula1-data-2");
+
+    let response = warp::test::request()
+        .method("PUT")
+        .path("/sources/1/schedule")
+        .json(&serde_json::json!({
+            "schedule": {"type": "cron", "expression": "0 0 9 * * *", "timezone": "Asia/Tokyo"}
+        }))
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(body(&response)["schedule"]["type"], "cron");
+    // the epoch is 09:00 in Tokyo, so the next one is on the next day
+    assert_eq!(body(&response)["next_run"], "1970-01-02T00:00:00Z");
+    assert_eq!(body(&response)["enabled"], true);
+
+    let response = warp::test::request()
+        .method("PUT")
+        .path("/sources/1/schedule")
+        .json(&serde_json::json!({
+            "schedule": {"type": "cron", "expression": "0 0 9 * * *", "timezone": "Mars/Olympus"}
+        }))
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/sources/1/disable")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(body(&response)["enabled"], false);
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/sources/1/enable")
+        .reply(&api)
+        .await;
+    assert_eq!(body(&response)["enabled"], true);
+    assert_eq!(body(&response)["consecutive_failures"], 0);
+
+    let response = warp::test::request().path("/sources").reply(&api).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(body(&response).as_array().unwrap().len(), 1);
+
+    let response = warp::test::request()
+        .method("DELETE")
+        .path("/sources/1")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let response = warp::test::request().path("/sources/1").reply(&api).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/sources")
+        .body("not a sitemap")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // sitemaps which would fail every crawl are not stored
+    for sitemap in [
+        r#"{"_id": "f1", "startUrl": [], "selectors": []}"#,
+        r#"{"_id": "f1", "startUrl": ["https://formula1-data.com"], "selectors": [{"id": "title", "type": "SelectorTable", "selector": ".title", "multiple": false, "parentSelectors": ["_root"], "delay": 0}]}"#,
+        r#"{"_id": "f1", "startUrl": ["https://formula1-data.com"], "selectors": [{"id": "title", "type": "SelectorText", "selector": ".title[", "multiple": false, "parentSelectors": ["_root"], "delay": 0}]}"#,
+    ] {
+        let response = warp::test::request()
+            .method("POST")
+            .path("/sources")
+            .body(sitemap)
+            .reply(&api)
+            .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+    let response = warp::test::request().path("/sources").reply(&api).await;
+    assert!(body(&response).as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn rejections_test() {
+    let api = routes(Arc::new(Dispatcher::new(MemoryDataStore::new())));
+    let error = |response: &warp::http::Response<warp::hyper::body::Bytes>| {
+        body(response)["error"].as_str().unwrap().to_string()
+    };
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/sources")
+        .body(vec![b' '; 2 * 1024 * 1024])
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(error(&response), "payload too large");
+
+    let response = warp::test::request()
+        .method("PUT")
+        .path("/sources/1/schedule")
+        .header("content-type", "text/plain")
+        .body("{}")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    let response = warp::test::request()
+        .method("PUT")
+        .path("/sources/1/schedule")
+        .json(&serde_json::json!({"schedule": {"type": "weekly"}}))
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(error(&response).starts_with("Request body deserialize error"));
+
+    let response = warp::test::request()
+        .path("/contents?limit=many")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(error(&response), "invalid query");
+
+    let response = warp::test::request()
+        .method("PATCH")
+        .path("/sources")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+}
+
+#[tokio::test]
+async fn contents_test() {
+    let data_store = MemoryDataStore::new();
+    let source_id = data_store
+        .add_source(SelectorTree::new(sitemap("formula1-data")).unwrap())
+        .await
+        .unwrap();
+    data_store
+        .commit_job_result(source_id, fake_contents(source_id), vec![])
+        .await
+        .unwrap();
+    let api = routes(Arc::new(Dispatcher::new(data_store)));
+
+    let response = warp::test::request()
+        .path("/contents?source_id=1&limit=2")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(body(&response)["items"].as_array().unwrap().len(), 2);
+    assert_eq!(body(&response)["items"][0]["title"], "title 0");
+    assert_eq!(body(&response)["total"], 3);
+    let next_cursor = body(&response)["next_cursor"].as_str().unwrap().to_string();
+
+    let response = warp::test::request()
+        .path(&format!(
+            "/contents?source_id=1&limit=2&cursor={}",
+            next_cursor
+        ))
+        .reply(&api)
+        .await;
+    assert_eq!(body(&response)["items"].as_array().unwrap().len(), 1);
+    assert_eq!(body(&response)["items"][0]["title"], "title 2");
+    assert_eq!(body(&response)["next_cursor"], Value::Null);
+
+    let response = warp::test::request()
+        .path("/contents?q=TITLE%201&since=2020-08-01T00:00:00Z")
+        .reply(&api)
+        .await;
+    assert_eq!(body(&response)["items"][0]["title"], "title 1");
+    assert_eq!(body(&response)["total"], 1);
+
+    let response = warp::test::request()
+        .path("/contents?cursor=yesterday")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = warp::test::request()
+        .path("/contents?source_id=2")
+        .reply(&api)
+        .await;
+    assert_eq!(body(&response)["items"].as_array().unwrap().len(), 0);
+
+    // limit is clamped to 1..=100
+    let response = warp::test::request()
+        .path("/contents?limit=0")
+        .reply(&api)
+        .await;
+    assert_eq!(body(&response)["items"].as_array().unwrap().len(), 1);
+    assert!(body(&response)["next_cursor"].is_string());
+    let response = warp::test::request()
+        .path("/contents?limit=9223372036854775807")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(body(&response)["items"].as_array().unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn search_test() {
+    let data_store = MemoryDataStore::new();
+    let source_id = data_store
+        .add_source(SelectorTree::new(sitemap("formula1-data")).unwrap())
+        .await
+        .unwrap();
+    data_store
+        .commit_job_result(source_id, fake_contents(source_id), vec![])
+        .await
+        .unwrap();
+    let api = routes(Arc::new(Dispatcher::new(data_store)));
+
+    let response = warp::test::request()
+        .path("/search?q=body&limit=2")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(body(&response)["items"].as_array().unwrap().len(), 2);
+    assert_eq!(body(&response)["next_offset"], 2);
+    assert_eq!(
+        body(&response)["items"][0]["snippet"],
+        "<mark>body</mark> 0"
+    );
+
+    let response = warp::test::request()
+        .path("/search?q=body&limit=2&offset=2")
+        .reply(&api)
+        .await;
+    assert_eq!(body(&response)["items"].as_array().unwrap().len(), 1);
+    assert_eq!(body(&response)["items"][0]["content"]["title"], "title 2");
+    assert_eq!(body(&response)["next_offset"], Value::Null);
+
+    let response = warp::test::request()
+        .path("/search?q=body&source_id=2")
+        .reply(&api)
+        .await;
+    assert_eq!(body(&response)["items"].as_array().unwrap().len(), 0);
+
+    // words shorter than 3 characters can not be searched
+    let response = warp::test::request()
+        .path("/search?q=body%201")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = warp::test::request().path("/search").reply(&api).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // limit is clamped to 1..=100, so next_offset always moves forward
+    let response = warp::test::request()
+        .path("/search?q=body&limit=0")
+        .reply(&api)
+        .await;
+    assert_eq!(body(&response)["items"].as_array().unwrap().len(), 1);
+    assert_eq!(body(&response)["next_offset"], 1);
+    let response = warp::test::request()
+        .path("/search?q=body&limit=9223372036854775807")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(body(&response)["items"].as_array().unwrap().len(), 3);
+    assert_eq!(body(&response)["next_offset"], Value::Null);
+}
+
+#[tokio::test]
+async fn job_runs_test() {
+    let data_store = MemoryDataStore::new();
+    for (i, status) in [JobStatus::Failed, JobStatus::Succeeded].iter().enumerate() {
+        data_store
+            .add_job_run(JobRun {
+                source_id: 1,
+                started_at: Utc.with_ymd_and_hms(2020, 8, 1, i as u32, 0, 0).unwrap(),
+                finished_at: Utc.with_ymd_and_hms(2020, 8, 1, i as u32, 1, 0).unwrap(),
+                status: *status,
+                pages_fetched: 2,
+                contents_inserted: 1,
+                error: None,
+            })
+            .await
+            .unwrap();
+    }
+    let api = routes(Arc::new(Dispatcher::new(data_store)));
+
+    let response = warp::test::request()
+        .path("/sources/1/runs?limit=1")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(body(&response).as_array().unwrap().len(), 1);
+    assert_eq!(body(&response)[0]["status"], "succeeded");
+    assert_eq!(body(&response)[0]["started_at"], "2020-08-01T01:00:00Z");
+
+    // limit is clamped to 1..=100
+    let response = warp::test::request()
+        .path("/sources/1/runs?limit=-1")
+        .reply(&api)
+        .await;
+    assert_eq!(body(&response).as_array().unwrap().len(), 1);
+    let response = warp::test::request()
+        .path("/sources/1/runs?limit=9223372036854775807")
+        .reply(&api)
+        .await;
+    assert_eq!(body(&response).as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn internal_error_test() {
+    // a database without tables fails every query
+    let pool = Pool::builder()
+        .max_size(1)
+        .build(ConnectionManager::<SqliteConnection>::new(":memory:"))
+        .unwrap();
+    let api = routes(Arc::new(Dispatcher::new(DataStoreAdapter::new(pool))));
+
+    let response = warp::test::request().path("/sources").reply(&api).await;
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    // the database error is not sent to the client
+    assert_eq!(body(&response)["error"], "internal error");
+}
+
+#[tokio::test]
+async fn serve_test() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let dispatcher = Arc::new(Dispatcher::new(MemoryDataStore::new()));
+
+    // a port in use is an error instead of a panic
+    let err = serve(Arc::clone(&dispatcher), addr, async {})
+        .err()
+        .unwrap();
+    assert!(err
+        .to_string()
+        .starts_with(&format!("failed to listen on {}: ", addr)));
+
+    drop(listener);
+    let (bound, server) = serve(dispatcher, addr, async {}).unwrap();
+    assert_eq!(bound, addr);
+    server.await;
+}
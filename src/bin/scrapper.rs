@@ -0,0 +1,642 @@
+use ::lib::api;
+use ::lib::crawler::{self, Crawler, LocalFetcher, SelectorTree, WebFetcher};
+use ::lib::dispatcher::{connect, ContentFilter, DataStore, Dispatcher};
+use ::lib::entity::JobStatus;
+use ::lib::exporter::{content_columns, content_records, new_exporter, ExportFormat};
+use ::lib::feed::{self, FeedFormat};
+use anyhow::{anyhow, Context, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use dotenv::dotenv;
+use std::fs::{self, File};
+use std::io::{self, IsTerminal, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::watch;
+
+// exit codes besides 0. clap exits with 2 on a usage error
+const EXIT_ERROR: u8 = 1;
+const EXIT_JOB_FAILED: u8 = 3;
+
+// contents are read from the data store this many at a time on export
+const EXPORT_PAGE_SIZE: i64 = 500;
+
+// chrono panics on a duration of seconds near i64::MAX, so the recheck interval is bounded
+const MAX_RECHECK_INTERVAL: i64 = 100 * 365 * 24 * 60 * 60;
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "scrapper",
+    about = "scraping everything from web pages without coding",
+    after_help = "exit status: 0 on success, 1 on an error, 2 on a usage error, \
+                  3 when a job of `run` failed"
+)]
+struct Cli {
+    #[arg(
+        long,
+        global = true,
+        env = "DATABASE_URL",
+        help = "SQLite file or postgres:// URL of the data store"
+    )]
+    database_url: Option<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    #[command(about = "crawl a sitemap once without the data store")]
+    Crawl(CrawlArgs),
+    #[command(
+        about = "crawl a sitemap with limits, and show what each selector extracted",
+        long_about = "crawl a sitemap with limits, and show what each selector extracted. \
+                      neither the data store nor the skip list is used"
+    )]
+    Preview(PreviewArgs),
+    #[command(
+        about = "run the selectors of a sitemap against saved HTML, and show what each matched",
+        long_about = "run the selectors of a sitemap against saved HTML, and show what each matched. \
+                      PAGES is an HTML file of the start url, or a directory saved by \
+                      `wget --force-directories`, where https://example.com/a/b is \
+                      example.com/a/b or example.com/a/b.html, and https://example.com/a/ is \
+                      example.com/a/index.html"
+    )]
+    Test(TestArgs),
+    #[command(subcommand, about = "manage the sources in the data store")]
+    Source(SourceCommand),
+    #[command(about = "crawl the stale sources once, or a source now")]
+    Run(RunArgs),
+    #[command(about = "serve the HTTP API and crawl the stale sources until SIGTERM/SIGINT")]
+    Daemon(DaemonArgs),
+    #[command(about = "export the stored contents as feeds or a table")]
+    Export(ExportArgs),
+}
+
+#[derive(Args, Debug)]
+struct CrawlArgs {
+    #[arg(help = "sitemap JSON file")]
+    sitemap: PathBuf,
+    #[arg(short, long, help = "output file [default: stdout]")]
+    output: Option<PathBuf>,
+    #[arg(short, long, value_enum, default_value_t = CrawlFormat::Flat)]
+    format: CrawlFormat,
+    #[arg(long, help = "file to write the accessed URLs to")]
+    access_log: Option<PathBuf>,
+    #[arg(long, help = "JSON array of the URLs not to access")]
+    skip_urls: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct PreviewArgs {
+    #[arg(help = "sitemap JSON file")]
+    sitemap: PathBuf,
+    #[arg(
+        long,
+        default_value_t = 10,
+        help = "pages to fetch including the start url"
+    )]
+    max_pages: usize,
+    #[arg(long, help = "links to follow from the start url in a row")]
+    max_depth: Option<usize>,
+}
+
+#[derive(Args, Debug)]
+struct TestArgs {
+    #[arg(help = "sitemap JSON file")]
+    sitemap: PathBuf,
+    #[arg(help = "HTML file or directory of saved pages")]
+    pages: PathBuf,
+    #[arg(
+        long,
+        help = "links to follow from the start url in a row [default: 0 for a file, no limit for a directory]"
+    )]
+    max_depth: Option<usize>,
+}
+
+#[derive(Clone, Copy, ValueEnum, Debug)]
+enum CrawlFormat {
+    #[value(help = "JSON arrays of values in column order")]
+    Flat,
+    #[value(help = "JSON objects mirroring the selector tree")]
+    Nested,
+    Csv,
+    Jsonl,
+    Parquet,
+}
+
+#[derive(Subcommand, Debug)]
+enum SourceCommand {
+    #[command(about = "list the sources")]
+    List,
+    #[command(about = "show a source as JSON")]
+    Show { id: i32 },
+    #[command(about = "add a source from a sitemap, and print its id")]
+    Add { sitemap: PathBuf },
+    #[command(about = "replace the sitemap of a source, keeping its name")]
+    Update { id: i32, sitemap: PathBuf },
+    #[command(about = "rename a source")]
+    Rename { id: i32, name: String },
+    #[command(about = "enable a source and crawl it on the next run")]
+    Enable { id: i32 },
+    #[command(about = "disable a source")]
+    Disable { id: i32 },
+    #[command(about = "remove a source with its contents, revisions and job runs")]
+    Remove { id: i32 },
+}
+
+#[derive(Args, Debug)]
+struct RunArgs {
+    #[arg(long, help = "crawl this source now even if it is not stale")]
+    source: Option<i32>,
+    #[arg(long, env = "CONCURRENCY", default_value_t = 8)]
+    concurrency: usize,
+    #[arg(
+        long,
+        env = "MAX_FAILURES",
+        default_value_t = 5,
+        help = "disable a source after this many failures in a row"
+    )]
+    max_failures: i32,
+    #[arg(
+        long,
+        env = "RECHECK_INTERVAL",
+        default_value_t = 7 * 24 * 60 * 60,
+        value_parser = clap::value_parser!(i64).range(0..=MAX_RECHECK_INTERVAL),
+        help = "seconds before a crawled page is fetched again to find changes"
+    )]
+    recheck_interval: i64,
+}
+
+#[derive(Args, Debug)]
+struct DaemonArgs {
+    #[arg(long, env = "LISTEN_ADDR", default_value = "127.0.0.1:3030")]
+    listen: SocketAddr,
+    #[arg(
+        long,
+        env = "POLL_INTERVAL",
+        default_value_t = 60,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "seconds between the polls for stale sources"
+    )]
+    poll_interval: u64,
+    #[arg(long, env = "CONCURRENCY", default_value_t = 8)]
+    concurrency: usize,
+    #[arg(
+        long,
+        env = "MAX_FAILURES",
+        default_value_t = 5,
+        help = "disable a source after this many failures in a row"
+    )]
+    max_failures: i32,
+    #[arg(
+        long,
+        env = "RECHECK_INTERVAL",
+        default_value_t = 7 * 24 * 60 * 60,
+        value_parser = clap::value_parser!(i64).range(0..=MAX_RECHECK_INTERVAL),
+        help = "seconds before a crawled page is fetched again to find changes"
+    )]
+    recheck_interval: i64,
+}
+
+#[derive(Args, Debug)]
+struct ExportArgs {
+    #[arg(short, long, value_enum, default_value_t = ExportTo::Rss)]
+    format: ExportTo,
+    #[arg(long, help = "export only this source")]
+    source: Option<i32>,
+    #[arg(
+        long,
+        help = "latest contents per feed, or in the table [default: 20 per feed, all in the table]"
+    )]
+    limit: Option<i64>,
+    #[arg(
+        short,
+        long,
+        help = "directory of the feeds [default: feeds], or file of the table [default: stdout]"
+    )]
+    output: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, ValueEnum, Debug)]
+enum ExportTo {
+    #[value(help = "an RSS file per source")]
+    Rss,
+    #[value(help = "an Atom file per source")]
+    Atom,
+    Csv,
+    Jsonl,
+    Parquet,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    dotenv().ok();
+    env_logger::init();
+
+    let cli = Cli::parse();
+    match execute(cli).await {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("error: {:#}", err);
+            ExitCode::from(EXIT_ERROR)
+        }
+    }
+}
+
+async fn execute(cli: Cli) -> Result<ExitCode> {
+    let database_url = cli.database_url;
+    let data_store = || {
+        database_url
+            .as_deref()
+            .filter(|url| !url.is_empty())
+            .ok_or_else(|| anyhow!("--database-url or DATABASE_URL is required"))
+            .and_then(connect)
+    };
+
+    match cli.command {
+        Command::Crawl(args) => crawl(args).await?,
+        Command::Preview(args) => preview(args).await?,
+        Command::Test(args) => test(args).await?,
+        Command::Source(command) => source(data_store()?, command).await?,
+        Command::Run(args) => return run(data_store()?, args).await,
+        Command::Daemon(args) => daemon(data_store()?, args).await?,
+        Command::Export(args) => export(data_store()?, args).await?,
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn create_output(path: Option<&Path>) -> Result<Box<dyn Write + Send>> {
+    Ok(match path {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout()),
+    })
+}
+
+fn read_file(path: &Path) -> Result<String> {
+    fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))
+}
+
+fn read_sitemap(path: &Path) -> Result<SelectorTree> {
+    SelectorTree::new(read_file(path)?)
+}
+
+async fn crawl(args: CrawlArgs) -> Result<()> {
+    let selector = read_sitemap(&args.sitemap)?;
+    let skip_urls: Vec<String> = match &args.skip_urls {
+        Some(path) => serde_json::from_str(&read_file(path)?)?,
+        None => vec![],
+    };
+    let mut output = create_output(args.output.as_deref())?;
+
+    let (artifacts, access_log, _) = Crawler::new(WebFetcher::new(), skip_urls)
+        .crawl(&selector)
+        .await?;
+    if let Some(path) = &args.access_log {
+        fs::write(path, serde_json::to_string_pretty(&access_log)?)?;
+    }
+
+    let format = match args.format {
+        CrawlFormat::Nested => {
+            let nested = crawler::format_nested(artifacts, &selector);
+            output.write_all(serde_json::to_string_pretty(&nested)?.as_bytes())?;
+            return Ok(());
+        }
+        CrawlFormat::Flat => None,
+        CrawlFormat::Csv => Some(ExportFormat::Csv),
+        CrawlFormat::Jsonl => Some(ExportFormat::JsonLines),
+        CrawlFormat::Parquet => Some(ExportFormat::Parquet),
+    };
+
+    let columns = columns(&selector);
+    let dropped = match format {
+        None => {
+            let formatted = crawler::format_with_policy(artifacts, &columns)?;
+            // arrays of values in column order
+            let rows: Vec<&[Option<Arc<crawler::Value>>]> =
+                formatted.rows.iter().map(crawler::Record::values).collect();
+            output.write_all(serde_json::to_string_pretty(&rows)?.as_bytes())?;
+            formatted.dropped
+        }
+        Some(format) => {
+            let names: Vec<String> = columns.iter().map(|col| col.name.clone()).collect();
+            let mut exporter = new_exporter(format, &names, output)?;
+            let dropped =
+                crawler::format_each(artifacts, &columns, |record| exporter.write(&record));
+            // finished even on an error, so that the output is a complete file
+            let finished = exporter.finish();
+            let dropped = dropped?;
+            finished?;
+            dropped
+        }
+    };
+    for row in dropped {
+        eprintln!("dropped {}", row);
+    }
+
+    Ok(())
+}
+
+fn columns(selector: &SelectorTree) -> Vec<crawler::Column> {
+    if selector.fields.is_empty() {
+        vec!["title", "link", "pub_date", "source_url"]
+            .into_iter()
+            .map(crawler::Column::required)
+            .collect()
+    } else {
+        selector.fields.iter().map(|f| f.to_column()).collect()
+    }
+}
+
+// prints the artifact tree, the rows and how each selector matched
+async fn preview(args: PreviewArgs) -> Result<()> {
+    let selector = read_sitemap(&args.sitemap)?;
+    let mut crawler = Crawler::new(WebFetcher::new(), vec![]).with_max_pages(args.max_pages);
+    if let Some(max_depth) = args.max_depth {
+        crawler = crawler.with_max_depth(max_depth);
+    }
+    let preview = crawler.preview(&selector).await?;
+
+    println!("# artifacts");
+    println!("{}", crawler::render_tree(&preview.artifacts));
+
+    println!();
+    println!("# rows");
+    let columns = columns(&selector);
+    let names: Vec<String> = columns.iter().map(|col| col.name.clone()).collect();
+    let highlight = io::stdout().is_terminal();
+    let report = crawler::render_report(&selector, &preview, highlight);
+    match crawler::format_with_policy(preview.artifacts, &columns) {
+        Ok(formatted) => {
+            println!("{}", names.join("\t"));
+            for row in &formatted.rows {
+                let values = row
+                    .values()
+                    .iter()
+                    .map(|value| value.as_ref().map(|v| v.to_string()).unwrap_or_default())
+                    .collect::<Vec<String>>();
+                println!("{}", values.join("\t"));
+            }
+            for row in formatted.dropped {
+                println!("dropped {}", row);
+            }
+        }
+        Err(err) => println!("{}", err),
+    }
+
+    println!();
+    println!("# selectors");
+    println!("{}", report);
+
+    Ok(())
+}
+
+// prints the values each selector extracted from the saved pages
+async fn test(args: TestArgs) -> Result<()> {
+    let selector = read_sitemap(&args.sitemap)?;
+    let crawler = if args.pages.is_dir() {
+        let crawler = Crawler::new(LocalFetcher::dir(&args.pages), vec![]);
+        match args.max_depth {
+            Some(max_depth) => crawler.with_max_depth(max_depth),
+            None => crawler,
+        }
+    } else {
+        // only the start url is saved, so links are not followed unless asked to
+        Crawler::new(LocalFetcher::file(&selector.start_url, &args.pages), vec![])
+            .with_max_depth(args.max_depth.unwrap_or(0))
+    };
+    let preview = crawler.preview(&selector).await?;
+
+    let highlight = io::stdout().is_terminal();
+    println!(
+        "{}",
+        crawler::render_matches(&selector, &preview, highlight)
+    );
+
+    Ok(())
+}
+
+async fn source<D: DataStore>(data_store: D, command: SourceCommand) -> Result<()> {
+    let found = |found: bool, id: i32| {
+        if found {
+            Ok(())
+        } else {
+            Err(anyhow!("source {} not found", id))
+        }
+    };
+
+    match command {
+        SourceCommand::List => {
+            for source in data_store.get_sources().await? {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}",
+                    source.id,
+                    source.name,
+                    if source.enabled {
+                        "enabled"
+                    } else {
+                        "disabled"
+                    },
+                    source.next_run.to_rfc3339(),
+                    source.url
+                );
+            }
+        }
+        SourceCommand::Show { id } => {
+            let source = data_store
+                .get_source(id)
+                .await?
+                .ok_or_else(|| anyhow!("source {} not found", id))?;
+            println!("{}", serde_json::to_string_pretty(&source)?);
+        }
+        SourceCommand::Add { sitemap } => {
+            let selector_tree = read_sitemap(&sitemap)?;
+            selector_tree.validate()?;
+            let id = data_store.add_source(selector_tree).await?;
+            println!("{}", id);
+        }
+        SourceCommand::Update { id, sitemap } => {
+            let selector_tree = read_sitemap(&sitemap)?;
+            selector_tree.validate()?;
+            found(data_store.update_source(id, selector_tree).await?, id)?;
+        }
+        SourceCommand::Rename { id, name } => {
+            found(data_store.rename_source(id, name).await?, id)?;
+        }
+        SourceCommand::Enable { id } => {
+            found(data_store.set_enabled(id, true).await?, id)?;
+        }
+        SourceCommand::Disable { id } => {
+            found(data_store.set_enabled(id, false).await?, id)?;
+        }
+        SourceCommand::Remove { id } => {
+            found(data_store.remove_source(id).await?, id)?;
+        }
+    }
+
+    Ok(())
+}
+
+// prints a line per job run
+async fn run<D: DataStore>(data_store: D, args: RunArgs) -> Result<ExitCode> {
+    let dispatcher = Dispatcher::new(data_store)
+        .with_concurrency(args.concurrency)
+        .with_max_failures(args.max_failures)
+        .with_recheck_interval(chrono::Duration::seconds(args.recheck_interval));
+    let job_runs = match args.source {
+        Some(id) => {
+            let source = dispatcher
+                .data_store()
+                .get_source(id)
+                .await?
+                .ok_or_else(|| anyhow!("source {} not found", id))?;
+            vec![dispatcher.run(source).await?]
+        }
+        None => dispatcher.start().await?,
+    };
+
+    for job_run in &job_runs {
+        println!(
+            "{}\t{:?}\t{} pages\t{} new contents{}",
+            job_run.source_id,
+            job_run.status,
+            job_run.pages_fetched,
+            job_run.contents_inserted,
+            job_run
+                .error
+                .as_ref()
+                .map(|error| format!("\t{}", error))
+                .unwrap_or_default()
+        );
+    }
+    if job_runs
+        .iter()
+        .any(|job_run| job_run.status == JobStatus::Failed)
+    {
+        return Ok(ExitCode::from(EXIT_JOB_FAILED));
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+async fn daemon<D>(data_store: D, args: DaemonArgs) -> Result<()>
+where
+    D: DataStore + Send + Sync + 'static,
+{
+    let dispatcher = Arc::new(
+        Dispatcher::new(data_store)
+            .with_concurrency(args.concurrency)
+            .with_max_failures(args.max_failures)
+            .with_recheck_interval(chrono::Duration::seconds(args.recheck_interval)),
+    );
+
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = terminate.recv() => {},
+            _ = interrupt.recv() => {},
+        }
+        log::info!("shutting down after in-flight jobs");
+        let _ = shutdown_tx.send(true);
+    });
+
+    let (addr, server) = api::serve(
+        Arc::clone(&dispatcher),
+        args.listen,
+        shutdown(shutdown_rx.clone()),
+    )?;
+    log::info!("listening on {}", addr);
+    tokio::join!(
+        server,
+        dispatcher.run_forever(
+            Duration::from_secs(args.poll_interval),
+            shutdown(shutdown_rx)
+        ),
+    );
+
+    Ok(())
+}
+
+async fn shutdown(mut rx: watch::Receiver<bool>) {
+    while !*rx.borrow() {
+        if rx.changed().await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn export<D: DataStore>(data_store: D, args: ExportArgs) -> Result<()> {
+    let format = match args.format {
+        ExportTo::Rss => FeedFormat::Rss,
+        ExportTo::Atom => FeedFormat::Atom,
+        ExportTo::Csv => return export_table(data_store, ExportFormat::Csv, args).await,
+        ExportTo::Jsonl => return export_table(data_store, ExportFormat::JsonLines, args).await,
+        ExportTo::Parquet => return export_table(data_store, ExportFormat::Parquet, args).await,
+    };
+
+    let output_dir = args.output.unwrap_or_else(|| PathBuf::from("feeds"));
+    let sources = match args.source {
+        Some(id) => vec![data_store
+            .get_source(id)
+            .await?
+            .ok_or_else(|| anyhow!("source {} not found", id))?],
+        None => data_store.get_sources().await?,
+    };
+
+    fs::create_dir_all(&output_dir)?;
+    for source in sources {
+        let contents = data_store
+            .query_contents(
+                ContentFilter::source(source.id),
+                None,
+                args.limit.unwrap_or(20),
+            )
+            .await?
+            .items;
+        fs::write(
+            output_dir.join(feed::filename(format, &source)),
+            feed::render(format, &source, &contents),
+        )?;
+    }
+
+    Ok(())
+}
+
+// contents from the newest, page by page
+async fn export_table<D: DataStore>(
+    data_store: D,
+    format: ExportFormat,
+    args: ExportArgs,
+) -> Result<()> {
+    let filter = ContentFilter {
+        source_id: args.source,
+        ..ContentFilter::default()
+    };
+    let mut exporter = new_exporter(
+        format,
+        &content_columns(),
+        create_output(args.output.as_deref())?,
+    )?;
+
+    let mut remaining = args.limit.unwrap_or(i64::MAX);
+    let mut cursor = None;
+    while remaining > 0 {
+        let page = data_store
+            .query_contents(filter.clone(), cursor, remaining.min(EXPORT_PAGE_SIZE))
+            .await?;
+        for record in content_records(&page.items) {
+            exporter.write(&record)?;
+        }
+        remaining -= page.items.len() as i64;
+        cursor = match page.next_cursor {
+            Some(next_cursor) => Some(next_cursor),
+            None => break,
+        };
+    }
+    exporter.finish()?;
+
+    Ok(())
+}
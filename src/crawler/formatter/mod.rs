@@ -1,70 +1,406 @@
 #[cfg(test)]
 mod test;
 
-use crate::crawler::Artifact;
-use anyhow::{anyhow, Result};
+use crate::crawler::{Artifact, Record, SelectorNode, SelectorTree, SelectorType, Value};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value as JsonValue};
 use std::collections::HashMap;
+use std::error;
+use std::fmt;
 use std::sync::Arc;
 
-pub fn format(
-    artifacts: Vec<Artifact>,
-    column: Vec<&'static str>,
-) -> Result<Vec<Vec<Arc<String>>>> {
-    let mut result = Vec::with_capacity(artifacts.len());
-
-    let contents = format_map_list(artifacts);
-
-    for content in contents {
-        let mut row = Vec::with_capacity(column.len());
-        for col in &column {
-            if let Some(v) = content.get(*col) {
-                row.push(v.clone());
-            } else {
-                return Err(anyhow!("field '{}' not found in {:?}", col, content));
+pub fn format(artifacts: Vec<Artifact>, column: Vec<&'static str>) -> Result<Vec<Record>> {
+    let columns = column
+        .into_iter()
+        .map(Column::required)
+        .collect::<Vec<Column>>();
+
+    Ok(format_with_policy(artifacts, &columns)?.rows)
+}
+
+// how to treat a row which does not have the column
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum ColumnPolicy {
+    // fail the whole batch
+    #[default]
+    Required,
+    // leave the value None
+    Optional,
+    // fill the value with the default
+    Default(String),
+    // drop the row
+    SkipRow,
+}
+
+// how to treat a column which has several values in a row (ex: SelectorImage with multiple)
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum MultiValue {
+    // join the values into a text with the separator
+    Join(String),
+    // keep the values as a Value::List
+    Array,
+    // output one row per value
+    FanOut,
+}
+
+impl Default for MultiValue {
+    fn default() -> Self {
+        MultiValue::Join(" ".to_string())
+    }
+}
+
+impl MultiValue {
+    // returns the candidates of the column, which has more than one only for FanOut
+    fn resolve(&self, values: &[Arc<Value>]) -> Vec<Arc<Value>> {
+        match self {
+            // a single value keeps its type
+            MultiValue::Join(_) if values.len() == 1 => values.to_vec(),
+            MultiValue::Join(separator) => {
+                let texts = values
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<String>>();
+                vec![Arc::new(texts.join(separator).into())]
+            }
+            MultiValue::Array => {
+                let list = values.iter().map(|v| (**v).clone()).collect();
+                vec![Arc::new(Value::List(list))]
             }
+            MultiValue::FanOut => values.to_vec(),
         }
-        result.push(row);
     }
+}
 
-    Ok(result)
+#[derive(Clone, PartialEq, Debug)]
+pub struct Column {
+    pub name: String,
+    pub policy: ColumnPolicy,
+    pub multi: MultiValue,
 }
 
-// flatten artifact tree to list of key-value
-fn format_map_list(artifacts: Vec<Artifact>) -> Vec<HashMap<String, Arc<String>>> {
-    let mut result: Vec<HashMap<String, Arc<String>>> = Vec::with_capacity(artifacts.len());
+impl Column {
+    pub fn new(name: &str, policy: ColumnPolicy) -> Self {
+        Column {
+            name: name.to_string(),
+            policy,
+            multi: MultiValue::default(),
+        }
+    }
+
+    pub fn required(name: &str) -> Self {
+        Column::new(name, ColumnPolicy::Required)
+    }
+}
 
-    // set of artifacts which do not have children
-    let mut leaves: Vec<Artifact> = vec![];
+// a row which was not output, with the columns it was missing
+#[derive(Clone, PartialEq, Debug)]
+pub struct DroppedRow {
+    pub index: usize,
+    pub missing: Vec<String>,
+}
 
-    for artifact in artifacts {
-        // skip leaf node and "source_url" node to push all rows after all
-        if artifact.children.len() == 0 && artifact.tag != "source_url" {
-            leaves.push(artifact);
-            continue;
+impl fmt::Display for DroppedRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "row {} misses {:?}", self.index, self.missing)
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub struct Formatted {
+    pub rows: Vec<Record>,
+    pub dropped: Vec<DroppedRow>,
+}
+
+// rows which missed required columns, and rows dropped by SkipRow until then
+#[derive(PartialEq, Debug)]
+pub struct FormatError {
+    pub failed: Vec<DroppedRow>,
+    pub dropped: Vec<DroppedRow>,
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failed = self
+            .failed
+            .iter()
+            .map(DroppedRow::to_string)
+            .collect::<Vec<String>>();
+        write!(f, "required field not found: {}", failed.join(", "))
+    }
+}
+
+impl error::Error for FormatError {}
+
+pub fn format_with_policy(
+    artifacts: Vec<Artifact>,
+    columns: &[Column],
+) -> Result<Formatted, FormatError> {
+    let names = Arc::new(columns.iter().map(|col| col.name.clone()).collect());
+    let mut rows = vec![];
+    let mut dropped = vec![];
+    let mut failed = vec![];
+
+    for (index, content) in map_rows(&artifacts).enumerate() {
+        match format_row(index, content, columns, &names) {
+            FormattedRow::Records(mut records) => rows.append(&mut records),
+            FormattedRow::Dropped(row) => dropped.push(row),
+            FormattedRow::Failed(row) => failed.push(row),
         }
+    }
 
-        let mut children = format_map_list(artifact.children);
+    if !failed.is_empty() {
+        return Err(FormatError { failed, dropped });
+    }
+    Ok(Formatted { rows, dropped })
+}
 
-        for child in &mut children {
-            // ex: ElementType not included in result
-            if let Some(data) = &artifact.data {
-                child.insert(artifact.tag.clone(), data.clone());
-            }
+// same as format_with_policy(), but passes each record to f as soon as it is made instead of
+// collecting them. the rows are checked for required columns first, so f is not called at all
+// when the format fails.
+pub fn format_each<F>(
+    artifacts: Vec<Artifact>,
+    columns: &[Column],
+    mut f: F,
+) -> Result<Vec<DroppedRow>>
+where
+    F: FnMut(Record) -> Result<()>,
+{
+    let mut dropped = vec![];
+    let mut failed = vec![];
+    for (index, content) in map_rows(&artifacts).enumerate() {
+        let (missing_required, missing_skip) = missing_columns(&content, columns);
+        if !missing_required.is_empty() {
+            failed.push(DroppedRow {
+                index,
+                missing: missing_required,
+            });
+        } else if !missing_skip.is_empty() {
+            dropped.push(DroppedRow {
+                index,
+                missing: missing_skip,
+            });
         }
-        result.append(&mut children)
+    }
+    if !failed.is_empty() {
+        return Err(FormatError { failed, dropped }.into());
     }
 
-    if leaves.len() != 0 {
-        let mut map: HashMap<String, Arc<String>> = HashMap::new();
-        for leaf in leaves {
-            // ex: ElementType not included in result
-            if let Some(data) = leaf.data {
-                map.insert(leaf.tag, data);
+    let names = Arc::new(columns.iter().map(|col| col.name.clone()).collect());
+    for (index, content) in map_rows(&artifacts).enumerate() {
+        if let FormattedRow::Records(records) = format_row(index, content, columns, &names) {
+            for record in records {
+                f(record)?;
             }
         }
+    }
+
+    Ok(dropped)
+}
+
+enum FormattedRow {
+    Records(Vec<Record>),
+    Dropped(DroppedRow),
+    Failed(DroppedRow),
+}
+
+fn format_row(
+    index: usize,
+    content: HashMap<String, Vec<Arc<Value>>>,
+    columns: &[Column],
+    names: &Arc<Vec<String>>,
+) -> FormattedRow {
+    let (missing_required, missing_skip) = missing_columns(&content, columns);
+    if !missing_required.is_empty() {
+        FormattedRow::Failed(DroppedRow {
+            index,
+            missing: missing_required,
+        })
+    } else if !missing_skip.is_empty() {
+        FormattedRow::Dropped(DroppedRow {
+            index,
+            missing: missing_skip,
+        })
+    } else {
+        // candidates of each column
+        let row = columns
+            .iter()
+            .map(|col| match (content.get(&col.name), &col.policy) {
+                (Some(values), _) => col.multi.resolve(values).into_iter().map(Some).collect(),
+                (None, ColumnPolicy::Default(default)) => {
+                    vec![Some(Arc::new(default.clone().into()))]
+                }
+                (None, _) => vec![None],
+            })
+            .collect();
+        FormattedRow::Records(
+            fan_out(row)
+                .into_iter()
+                .map(|values| Record::new(Arc::clone(names), values))
+                .collect(),
+        )
+    }
+}
+
+// columns missing in the row, which are Required and SkipRow
+fn missing_columns(
+    content: &HashMap<String, Vec<Arc<Value>>>,
+    columns: &[Column],
+) -> (Vec<String>, Vec<String>) {
+    let mut missing_required = vec![];
+    let mut missing_skip = vec![];
+    for col in columns
+        .iter()
+        .filter(|col| !content.contains_key(&col.name))
+    {
+        match col.policy {
+            ColumnPolicy::Required => missing_required.push(col.name.clone()),
+            ColumnPolicy::SkipRow => missing_skip.push(col.name.clone()),
+            ColumnPolicy::Optional | ColumnPolicy::Default(_) => {}
+        }
+    }
+    (missing_required, missing_skip)
+}
+
+// cartesian product of the candidates of each column
+fn fan_out(row: Vec<Vec<Option<Arc<Value>>>>) -> Vec<Vec<Option<Arc<Value>>>> {
+    row.into_iter().fold(vec![vec![]], |partials, candidates| {
+        partials
+            .iter()
+            .flat_map(|partial| {
+                candidates.iter().map(move |candidate| {
+                    let mut values = partial.clone();
+                    values.push(candidate.clone());
+                    values
+                })
+            })
+            .collect()
+    })
+}
+
+// nest artifacts as JSON objects mirroring the selector tree, one object per root artifact.
+// children are keyed by selector id, and a node which has both data and children (ex: LinkType)
+// keeps its own data under its own id. multiple selectors become arrays, except TextType which
+// already joins the matched texts into one value.
+pub fn format_nested(artifacts: Vec<Artifact>, selector_tree: &SelectorTree) -> Vec<JsonValue> {
+    artifacts
+        .into_iter()
+        .map(|artifact| {
+            let mut object = nest_children(artifact.children, &selector_tree.selectors);
+            if let Some(data) = artifact.data {
+                object.insert(artifact.tag, data.to_json());
+            }
+            JsonValue::Object(object)
+        })
+        .collect()
+}
 
-        result.push(map);
+fn nest_children(artifacts: Vec<Artifact>, nodes: &[SelectorNode]) -> Map<String, JsonValue> {
+    let mut grouped: HashMap<String, Vec<Artifact>> = HashMap::new();
+    for artifact in artifacts {
+        grouped
+            .entry(artifact.tag.clone())
+            .or_default()
+            .push(artifact);
     }
 
-    result
+    let mut object = Map::new();
+    for node in nodes {
+        let values = grouped
+            .remove(&node.id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|artifact| nest_artifact(artifact, node))
+            .collect::<Vec<JsonValue>>();
+
+        let value = if node.multiple && node.selector_type != SelectorType::Text {
+            JsonValue::Array(values)
+        } else {
+            values.into_iter().next().unwrap_or(JsonValue::Null)
+        };
+        object.insert(node.id.clone(), value);
+    }
+
+    object
+}
+
+fn nest_artifact(artifact: Artifact, node: &SelectorNode) -> JsonValue {
+    let data = artifact.data.map(|data| data.to_json());
+    if node.children.is_empty() {
+        return data.unwrap_or(JsonValue::Null);
+    }
+
+    let mut object = nest_children(artifact.children, &node.children);
+    if let Some(data) = data {
+        object.insert(node.id.clone(), data);
+    }
+    JsonValue::Object(object)
+}
+
+// flatten artifact tree to key-values, one per row. leaves sharing a tag keep all of their values
+fn map_rows(artifacts: &[Artifact]) -> MapRows<'_> {
+    MapRows {
+        frames: vec![MapRowsFrame {
+            artifacts: artifacts.iter(),
+            data: None,
+            leaves: None,
+        }],
+    }
+}
+
+// walks the artifact tree depth first. the rows of the children of an artifact come in order,
+// and then a row of the leaves at the same level
+struct MapRows<'a> {
+    frames: Vec<MapRowsFrame<'a>>,
+}
+
+struct MapRowsFrame<'a> {
+    artifacts: std::slice::Iter<'a, Artifact>,
+    // tag and data of the artifact which has these artifacts as children
+    data: Option<(&'a str, &'a Arc<Value>)>,
+    leaves: Option<HashMap<String, Vec<Arc<Value>>>>,
+}
+
+impl Iterator for MapRows<'_> {
+    type Item = HashMap<String, Vec<Arc<Value>>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let frame = self.frames.last_mut()?;
+            match frame.artifacts.next() {
+                // "source_url" node is not a leaf even without children
+                Some(artifact) if artifact.children.is_empty() && artifact.tag != "source_url" => {
+                    let leaves = frame.leaves.get_or_insert_with(HashMap::new);
+                    // ex: ElementType not included in result
+                    if let Some(data) = &artifact.data {
+                        leaves
+                            .entry(artifact.tag.clone())
+                            .or_default()
+                            .push(Arc::clone(data));
+                    }
+                }
+                Some(artifact) => self.frames.push(MapRowsFrame {
+                    artifacts: artifact.children.iter(),
+                    data: artifact
+                        .data
+                        .as_ref()
+                        .map(|data| (artifact.tag.as_str(), data)),
+                    leaves: None,
+                }),
+                None => {
+                    let frame = self.frames.pop()?;
+                    let mut row = match frame.leaves {
+                        Some(row) => row,
+                        None => continue,
+                    };
+                    // ancestors from the nearest. a farther one overwrites a nearer one
+                    let ancestors = self.frames.iter().rev().filter_map(|frame| frame.data);
+                    for (tag, data) in frame.data.into_iter().chain(ancestors) {
+                        row.insert(tag.to_string(), vec![Arc::clone(data)]);
+                    }
+                    return Some(row);
+                }
+            }
+        }
+    }
 }
@@ -1,7 +1,19 @@
-use crate::crawler::format;
 use crate::crawler::Artifact;
+use crate::crawler::SelectorTree;
+use crate::crawler::{
+    format, format_each, format_nested, format_with_policy, Column, ColumnPolicy, DroppedRow,
+    FormatError, Formatted, MultiValue, Record, Value,
+};
+use serde_json::json;
 use std::sync::Arc;
 
+fn records(column: &[&str], rows: Vec<Vec<Option<Arc<Value>>>>) -> Vec<Record> {
+    let column = Arc::new(column.iter().map(|c| c.to_string()).collect());
+    rows.into_iter()
+        .map(|row| Record::new(Arc::clone(&column), row))
+        .collect()
+}
+
 #[test]
 fn format_test() {
     let test_data = vec![
@@ -10,36 +22,36 @@ fn format_test() {
             vec!["title", "link", "body", "source_url"],
             vec![Artifact {
                 tag: "source_url".to_string(),
-                data: Some(Arc::new("http://url-root.com/article".to_string())),
+                data: Some(Arc::new("http://url-root.com/article".into())),
                 children: vec![
                     Artifact {
                         tag: "link".to_string(),
-                        data: Some(Arc::new("http://url-a.com".to_string())),
+                        data: Some(Arc::new("http://url-a.com".into())),
                         children: vec![
                             Artifact {
                                 tag: "title".to_string(),
-                                data: Some(Arc::new("title A".to_string())),
+                                data: Some(Arc::new("title A".into())),
                                 children: vec![],
                             },
                             Artifact {
                                 tag: "body".to_string(),
-                                data: Some(Arc::new("body A1 body A2".to_string())),
+                                data: Some(Arc::new("body A1 body A2".into())),
                                 children: vec![],
                             },
                         ],
                     },
                     Artifact {
                         tag: "link".to_string(),
-                        data: Some(Arc::new("http://url-b.com".to_string())),
+                        data: Some(Arc::new("http://url-b.com".into())),
                         children: vec![
                             Artifact {
                                 tag: "title".to_string(),
-                                data: Some(Arc::new("title B".to_string())),
+                                data: Some(Arc::new("title B".into())),
                                 children: vec![],
                             },
                             Artifact {
                                 tag: "body".to_string(),
-                                data: Some(Arc::new("body B1 body B2".to_string())),
+                                data: Some(Arc::new("body B1 body B2".into())),
                                 children: vec![],
                             },
                         ],
@@ -48,16 +60,16 @@ fn format_test() {
             }],
             vec![
                 vec![
-                    Arc::new("title A".to_string()),
-                    Arc::new("http://url-a.com".to_string()),
-                    Arc::new("body A1 body A2".to_string()),
-                    Arc::new("http://url-root.com/article".to_string()),
+                    Arc::new("title A".into()),
+                    Arc::new("http://url-a.com".into()),
+                    Arc::new("body A1 body A2".into()),
+                    Arc::new("http://url-root.com/article".into()),
                 ],
                 vec![
-                    Arc::new("title B".to_string()),
-                    Arc::new("http://url-b.com".to_string()),
-                    Arc::new("body B1 body B2".to_string()),
-                    Arc::new("http://url-root.com/article".to_string()),
+                    Arc::new("title B".into()),
+                    Arc::new("http://url-b.com".into()),
+                    Arc::new("body B1 body B2".into()),
+                    Arc::new("http://url-root.com/article".into()),
                 ],
             ],
         ),
@@ -66,7 +78,7 @@ fn format_test() {
             vec![],
             vec![Artifact {
                 tag: "source_url".to_string(),
-                data: Some(Arc::new("http://url-root.com/article".to_string())),
+                data: Some(Arc::new("http://url-root.com/article".into())),
                 children: vec![],
             }],
             vec![],
@@ -75,7 +87,7 @@ fn format_test() {
             vec!["title", "body", "source_url"],
             vec![Artifact {
                 tag: "source_url".to_string(),
-                data: Some(Arc::new("http://url-root.com/article".to_string())),
+                data: Some(Arc::new("http://url-root.com/article".into())),
                 children: vec![
                     Artifact {
                         tag: "content".to_string(),
@@ -83,12 +95,12 @@ fn format_test() {
                         children: vec![
                             Artifact {
                                 tag: "title".to_string(),
-                                data: Some(Arc::new("title a".to_string())),
+                                data: Some(Arc::new("title a".into())),
                                 children: vec![],
                             },
                             Artifact {
                                 tag: "body".to_string(),
-                                data: Some(Arc::new("body a".to_string())),
+                                data: Some(Arc::new("body a".into())),
                                 children: vec![],
                             },
                         ],
@@ -99,12 +111,12 @@ fn format_test() {
                         children: vec![
                             Artifact {
                                 tag: "title".to_string(),
-                                data: Some(Arc::new("title b".to_string())),
+                                data: Some(Arc::new("title b".into())),
                                 children: vec![],
                             },
                             Artifact {
                                 tag: "body".to_string(),
-                                data: Some(Arc::new("body b".to_string())),
+                                data: Some(Arc::new("body b".into())),
                                 children: vec![],
                             },
                         ],
@@ -115,12 +127,12 @@ fn format_test() {
                         children: vec![
                             Artifact {
                                 tag: "title".to_string(),
-                                data: Some(Arc::new("title c".to_string())),
+                                data: Some(Arc::new("title c".into())),
                                 children: vec![],
                             },
                             Artifact {
                                 tag: "body".to_string(),
-                                data: Some(Arc::new("body c".to_string())),
+                                data: Some(Arc::new("body c".into())),
                                 children: vec![],
                             },
                         ],
@@ -129,26 +141,401 @@ fn format_test() {
             }],
             vec![
                 vec![
-                    Arc::new("title a".to_string()),
-                    Arc::new("body a".to_string()),
-                    Arc::new("http://url-root.com/article".to_string()),
+                    Arc::new("title a".into()),
+                    Arc::new("body a".into()),
+                    Arc::new("http://url-root.com/article".into()),
                 ],
                 vec![
-                    Arc::new("title b".to_string()),
-                    Arc::new("body b".to_string()),
-                    Arc::new("http://url-root.com/article".to_string()),
+                    Arc::new("title b".into()),
+                    Arc::new("body b".into()),
+                    Arc::new("http://url-root.com/article".into()),
                 ],
                 vec![
-                    Arc::new("title c".to_string()),
-                    Arc::new("body c".to_string()),
-                    Arc::new("http://url-root.com/article".to_string()),
+                    Arc::new("title c".into()),
+                    Arc::new("body c".into()),
+                    Arc::new("http://url-root.com/article".into()),
                 ],
             ],
         ),
     ];
 
     for (column, artifacts, expected) in test_data {
+        let expected = records(
+            &column,
+            expected
+                .into_iter()
+                .map(|row| row.into_iter().map(Some).collect())
+                .collect(),
+        );
         let actual = format(artifacts, column).unwrap();
         assert_eq!(expected, actual)
     }
 }
+
+#[test]
+fn format_with_policy_test() {
+    let artifacts = || {
+        vec![Artifact {
+            tag: "source_url".to_string(),
+            data: Some(Arc::new("http://url-root.com/article".into())),
+            children: vec![
+                Artifact {
+                    tag: "content".to_string(),
+                    data: None,
+                    children: vec![
+                        Artifact {
+                            tag: "title".to_string(),
+                            data: Some(Arc::new("title a".into())),
+                            children: vec![],
+                        },
+                        Artifact {
+                            tag: "subtitle".to_string(),
+                            data: Some(Arc::new("subtitle a".into())),
+                            children: vec![],
+                        },
+                    ],
+                },
+                Artifact {
+                    tag: "content".to_string(),
+                    data: None,
+                    children: vec![Artifact {
+                        tag: "title".to_string(),
+                        data: Some(Arc::new("title b".into())),
+                        children: vec![],
+                    }],
+                },
+            ],
+        }]
+    };
+
+    let test_data = vec![
+        (
+            // optional
+            ColumnPolicy::Optional,
+            Ok(Formatted {
+                rows: records(
+                    &["title", "subtitle"],
+                    vec![
+                        vec![
+                            Some(Arc::new("title a".into())),
+                            Some(Arc::new("subtitle a".into())),
+                        ],
+                        vec![Some(Arc::new("title b".into())), None],
+                    ],
+                ),
+                dropped: vec![],
+            }),
+        ),
+        (
+            // default
+            ColumnPolicy::Default("no subtitle".to_string()),
+            Ok(Formatted {
+                rows: records(
+                    &["title", "subtitle"],
+                    vec![
+                        vec![
+                            Some(Arc::new("title a".into())),
+                            Some(Arc::new("subtitle a".into())),
+                        ],
+                        vec![
+                            Some(Arc::new("title b".into())),
+                            Some(Arc::new("no subtitle".into())),
+                        ],
+                    ],
+                ),
+                dropped: vec![],
+            }),
+        ),
+        (
+            // skip row
+            ColumnPolicy::SkipRow,
+            Ok(Formatted {
+                rows: records(
+                    &["title", "subtitle"],
+                    vec![vec![
+                        Some(Arc::new("title a".into())),
+                        Some(Arc::new("subtitle a".into())),
+                    ]],
+                ),
+                dropped: vec![DroppedRow {
+                    index: 1,
+                    missing: vec!["subtitle".to_string()],
+                }],
+            }),
+        ),
+        (
+            // required
+            ColumnPolicy::Required,
+            Err(FormatError {
+                failed: vec![DroppedRow {
+                    index: 1,
+                    missing: vec!["subtitle".to_string()],
+                }],
+                dropped: vec![],
+            }),
+        ),
+    ];
+
+    for (policy, expected) in test_data {
+        let columns = vec![
+            Column::required("title"),
+            Column::new("subtitle", policy.clone()),
+        ];
+        let actual = format_with_policy(artifacts(), &columns);
+        assert_eq!(expected, actual, "{:?}", policy);
+
+        // format_each passes the same records, and none of them if the format fails
+        let mut rows = vec![];
+        let actual = format_each(artifacts(), &columns, |record| {
+            rows.push(record);
+            Ok(())
+        });
+        match expected {
+            Ok(expected) => {
+                assert_eq!(expected.rows, rows, "{:?}", policy);
+                assert_eq!(expected.dropped, actual.unwrap(), "{:?}", policy);
+            }
+            Err(expected) => {
+                assert!(rows.is_empty(), "{:?}", policy);
+                let actual = actual.unwrap_err().downcast::<FormatError>().unwrap();
+                assert_eq!(expected, actual, "{:?}", policy);
+            }
+        }
+    }
+
+    // an error of f stops the format
+    let columns = vec![Column::required("title")];
+    let mut rows = 0;
+    let actual = format_each(artifacts(), &columns, |_| {
+        rows += 1;
+        Err(anyhow::anyhow!("disk full"))
+    });
+    assert_eq!(actual.unwrap_err().to_string(), "disk full");
+    assert_eq!(rows, 1);
+}
+
+#[test]
+fn format_nested_test() {
+    let selector_tree = SelectorTree::new(
+        r###"
+{
+  "_id": "test",
+  "startUrl": [
+    "http://url-root.com/article"
+  ],
+  "selectors": [
+    {
+      "id": "link",
+      "type": "SelectorLink",
+      "parentSelectors": [
+        "_root"
+      ],
+      "selector": ".url",
+      "multiple": true,
+      "delay": 0
+    },
+    {
+      "id": "title",
+      "type": "SelectorText",
+      "parentSelectors": [
+        "link"
+      ],
+      "selector": ".title",
+      "multiple": false,
+      "delay": 0
+    },
+    {
+      "id": "body",
+      "type": "SelectorText",
+      "parentSelectors": [
+        "link"
+      ],
+      "selector": ".body",
+      "multiple": true,
+      "delay": 0
+    },
+    {
+      "id": "image",
+      "type": "SelectorImage",
+      "parentSelectors": [
+        "link"
+      ],
+      "selector": "img",
+      "multiple": true,
+      "delay": 0
+    }
+  ]
+}
+    "###
+        .into(),
+    )
+    .unwrap();
+
+    let artifacts = vec![Artifact {
+        tag: "source_url".to_string(),
+        data: Some(Arc::new("http://url-root.com/article".into())),
+        children: vec![
+            Artifact {
+                tag: "link".to_string(),
+                data: Some(Arc::new("http://url-a.com".into())),
+                children: vec![
+                    Artifact {
+                        tag: "title".to_string(),
+                        data: Some(Arc::new("title A".into())),
+                        children: vec![],
+                    },
+                    Artifact {
+                        tag: "body".to_string(),
+                        data: Some(Arc::new("body A1 body A2".into())),
+                        children: vec![],
+                    },
+                    Artifact {
+                        tag: "image".to_string(),
+                        data: Some(Arc::new("http://image-a1.com".into())),
+                        children: vec![],
+                    },
+                    Artifact {
+                        tag: "image".to_string(),
+                        data: Some(Arc::new("http://image-a2.com".into())),
+                        children: vec![],
+                    },
+                ],
+            },
+            Artifact {
+                tag: "link".to_string(),
+                data: Some(Arc::new("http://url-b.com".into())),
+                children: vec![Artifact {
+                    tag: "body".to_string(),
+                    data: Some(Arc::new("body B1".into())),
+                    children: vec![],
+                }],
+            },
+        ],
+    }];
+
+    let expected = vec![json!({
+        "source_url": "http://url-root.com/article",
+        "link": [
+            {
+                "link": "http://url-a.com",
+                "title": "title A",
+                "body": "body A1 body A2",
+                "image": ["http://image-a1.com", "http://image-a2.com"],
+            },
+            {
+                "link": "http://url-b.com",
+                "title": null,
+                "body": "body B1",
+                "image": [],
+            },
+        ],
+    })];
+
+    assert_eq!(expected, format_nested(artifacts, &selector_tree))
+}
+
+#[test]
+fn format_multi_value_test() {
+    let artifacts = || {
+        vec![Artifact {
+            tag: "source_url".to_string(),
+            data: Some(Arc::new("http://url-root.com/article".into())),
+            children: vec![
+                Artifact {
+                    tag: "link".to_string(),
+                    data: Some(Arc::new("http://url-a.com".into())),
+                    children: vec![
+                        Artifact {
+                            tag: "image".to_string(),
+                            data: Some(Arc::new("http://image-a1.com".into())),
+                            children: vec![],
+                        },
+                        Artifact {
+                            tag: "image".to_string(),
+                            data: Some(Arc::new("http://image-a2.com".into())),
+                            children: vec![],
+                        },
+                    ],
+                },
+                Artifact {
+                    tag: "link".to_string(),
+                    data: Some(Arc::new("http://url-b.com".into())),
+                    children: vec![Artifact {
+                        tag: "image".to_string(),
+                        data: Some(Arc::new("http://image-b1.com".into())),
+                        children: vec![],
+                    }],
+                },
+            ],
+        }]
+    };
+
+    let test_data = vec![
+        (
+            // join
+            MultiValue::Join(",".to_string()),
+            vec![
+                vec![
+                    Some(Arc::new("http://url-a.com".into())),
+                    Some(Arc::new("http://image-a1.com,http://image-a2.com".into())),
+                ],
+                vec![
+                    Some(Arc::new("http://url-b.com".into())),
+                    Some(Arc::new("http://image-b1.com".into())),
+                ],
+            ],
+        ),
+        (
+            // array
+            MultiValue::Array,
+            vec![
+                vec![
+                    Some(Arc::new("http://url-a.com".into())),
+                    Some(Arc::new(Value::List(vec![
+                        "http://image-a1.com".into(),
+                        "http://image-a2.com".into(),
+                    ]))),
+                ],
+                vec![
+                    Some(Arc::new("http://url-b.com".into())),
+                    Some(Arc::new(Value::List(vec!["http://image-b1.com".into()]))),
+                ],
+            ],
+        ),
+        (
+            // fan out
+            MultiValue::FanOut,
+            vec![
+                vec![
+                    Some(Arc::new("http://url-a.com".into())),
+                    Some(Arc::new("http://image-a1.com".into())),
+                ],
+                vec![
+                    Some(Arc::new("http://url-a.com".into())),
+                    Some(Arc::new("http://image-a2.com".into())),
+                ],
+                vec![
+                    Some(Arc::new("http://url-b.com".into())),
+                    Some(Arc::new("http://image-b1.com".into())),
+                ],
+            ],
+        ),
+    ];
+
+    for (multi, expected) in test_data {
+        let columns = vec![
+            Column::required("link"),
+            Column {
+                multi: multi.clone(),
+                ..Column::required("image")
+            },
+        ];
+        let actual = format_with_policy(artifacts(), &columns).unwrap();
+        assert_eq!(
+            records(&["link", "image"], expected),
+            actual.rows,
+            "{:?}",
+            multi
+        )
+    }
+}
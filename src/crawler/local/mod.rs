@@ -0,0 +1,139 @@
+#[cfg(test)]
+mod test;
+
+use crate::crawler::FetchClient;
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use reqwest::Url;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// serves saved pages instead of the web, to try selectors without network
+pub struct LocalFetcher {
+    pages: LocalPages,
+    access_logs: Vec<String>,
+}
+
+// a page which is not saved. Crawler skips the link to it and reports it
+#[derive(Debug)]
+pub struct PageNotSaved {
+    pub url: String,
+    // files which were looked for
+    pub paths: Vec<PathBuf>,
+}
+
+impl fmt::Display for PageNotSaved {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "page not saved: {}", self.url)?;
+        if !self.paths.is_empty() {
+            let paths = self
+                .paths
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<String>>();
+            write!(f, " (looked for {})", paths.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for PageNotSaved {}
+
+enum LocalPages {
+    // a page served for the url
+    File { url: String, path: PathBuf },
+    // pages saved in the layout of `wget --force-directories`. see LocalFetcher::paths_of()
+    Dir(PathBuf),
+}
+
+impl LocalFetcher {
+    pub fn file<P: AsRef<Path>>(url: &str, path: P) -> Self {
+        LocalFetcher {
+            pages: LocalPages::File {
+                url: url.to_string(),
+                path: path.as_ref().to_path_buf(),
+            },
+            access_logs: vec![],
+        }
+    }
+
+    pub fn dir<P: AsRef<Path>>(root: P) -> Self {
+        LocalFetcher {
+            pages: LocalPages::Dir(root.as_ref().to_path_buf()),
+            access_logs: vec![],
+        }
+    }
+
+    // files which may hold the page of the url, in the order to be tried
+    // ex: https://formula1-data.com/article/1?page=2
+    //     -> root/formula1-data.com/article/1?page=2, root/formula1-data.com/article/1?page=2.html
+    //     https://formula1-data.com/article/
+    //     -> root/formula1-data.com/article/index.html
+    pub fn paths_of(root: &Path, url: &str) -> Result<Vec<PathBuf>> {
+        let url = Url::parse(url)?;
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return Err(anyhow!("url without host: {}", url)),
+        };
+        let mut path = url.path().trim_start_matches('/').to_string();
+        if path.is_empty() || path.ends_with('/') {
+            path.push_str("index.html");
+        }
+        if let Some(query) = url.query() {
+            path = format!("{}?{}", path, query);
+        }
+
+        let path = root.join(host).join(path);
+        let mut paths = vec![path.clone()];
+        if path.extension().is_none_or(|extension| extension != "html") {
+            let mut with_extension = path.into_os_string();
+            with_extension.push(".html");
+            paths.push(with_extension.into());
+        }
+        Ok(paths)
+    }
+
+    fn read(&self, url: &str) -> Result<String> {
+        let paths = match &self.pages {
+            LocalPages::File {
+                url: file_url,
+                path,
+            } if file_url == url => vec![path.clone()],
+            LocalPages::File { .. } => {
+                return Err(PageNotSaved {
+                    url: url.to_string(),
+                    paths: vec![],
+                }
+                .into())
+            }
+            LocalPages::Dir(root) => Self::paths_of(root, url)?,
+        };
+        match paths.iter().find(|path| path.is_file()) {
+            Some(path) => fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path.display())),
+            None => Err(PageNotSaved {
+                url: url.to_string(),
+                paths,
+            }
+            .into()),
+        }
+    }
+}
+
+#[async_trait]
+impl FetchClient for LocalFetcher {
+    async fn fetch(&mut self, url: &String, logging: bool) -> Result<String> {
+        let doc = self.read(url)?;
+        if logging {
+            self.access_logs.push(url.clone());
+        }
+        Ok(doc)
+    }
+
+    fn dump_access_logs(self) -> Vec<String> {
+        self.access_logs
+    }
+}
@@ -0,0 +1,176 @@
+use crate::crawler::{render_matches, Crawler, LocalFetcher, SelectorTree};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn selector_tree() -> SelectorTree {
+    SelectorTree::new(
+        r#"{
+  "_id": "test",
+  "startUrl": ["http://url-root.com/article/"],
+  "selectors": [
+    {"id": "link", "type": "SelectorLink", "parentSelectors": ["_root"], "selector": ".url", "multiple": true, "delay": 0},
+    {"id": "title", "type": "SelectorText", "parentSelectors": ["link"], "selector": ".title", "multiple": false, "regex": "", "delay": 0},
+    {"id": "author", "type": "SelectorText", "parentSelectors": ["link"], "selector": ".author", "multiple": false, "regex": "", "delay": 0}
+  ]
+}"#
+        .to_string(),
+    )
+    .unwrap()
+}
+
+// a directory of saved pages, removed when the test ends
+struct SavedPages(PathBuf);
+
+impl SavedPages {
+    fn new(name: &str, pages: &[(&str, &str)]) -> Self {
+        let root = env::temp_dir().join(format!(
+            "scrapper-local-test-{}-{}",
+            name,
+            std::process::id()
+        ));
+        for (path, html) in pages {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, html).unwrap();
+        }
+        SavedPages(root)
+    }
+
+    fn root(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for SavedPages {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn paths_of_test() {
+    let root = Path::new("/pages");
+    let paths = |url: &str| {
+        LocalFetcher::paths_of(root, url)
+            .unwrap()
+            .into_iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect::<Vec<String>>()
+    };
+    assert_eq!(
+        paths("https://formula1-data.com/article/1"),
+        vec![
+            "/pages/formula1-data.com/article/1",
+            "/pages/formula1-data.com/article/1.html",
+        ]
+    );
+    assert_eq!(
+        paths("https://formula1-data.com/article/"),
+        vec!["/pages/formula1-data.com/article/index.html"]
+    );
+    assert_eq!(
+        paths("http://localhost:8080?page=2"),
+        vec![
+            "/pages/localhost:8080/index.html?page=2",
+            "/pages/localhost:8080/index.html?page=2.html",
+        ]
+    );
+    assert_eq!(
+        paths("https://formula1-data.com/a.html"),
+        vec!["/pages/formula1-data.com/a.html"]
+    );
+    assert!(LocalFetcher::paths_of(root, "not a url").is_err());
+}
+
+#[tokio::test]
+async fn local_fetcher_dir_test() {
+    let pages = SavedPages::new(
+        "dir",
+        &[
+            (
+                "url-root.com/article/index.html",
+                r#"<a class="url" href="http://url-a.com/1">a</a>
+                   <a class="url" href="http://url-b.com/1">b</a>"#,
+            ),
+            ("url-a.com/1", r#"<p class="title">title A</p>"#),
+            ("url-b.com/1.html", r#"<p class="title">title B</p>"#),
+        ],
+    );
+    let selector_tree = selector_tree();
+    let preview = Crawler::new(LocalFetcher::dir(pages.root()), vec![])
+        .preview(&selector_tree)
+        .await
+        .unwrap();
+    assert_eq!(
+        preview.pages,
+        vec!["http://url-a.com/1", "http://url-b.com/1"]
+    );
+    assert_eq!(
+        render_matches(&selector_tree, &preview, false),
+        r#"link (Link ".url"): 2 matches in 1 of 1 documents
+  - http://url-a.com/1
+  - http://url-b.com/1
+  title (Text ".title"): 2 matches in 2 of 2 documents
+    - title A
+    - title B
+  author (Text ".author"): matched nothing in 2 documents
+    - ""
+    - """#
+    );
+
+    // a linked page not saved is skipped and reported
+    let pages = SavedPages::new(
+        "missing",
+        &[
+            (
+                "url-root.com/article/index.html",
+                r#"<a class="url" href="http://url-a.com/1">a</a>
+                   <a class="url" href="http://url-b.com/1">b</a>"#,
+            ),
+            ("url-b.com/1", r#"<p class="title">title B</p>"#),
+        ],
+    );
+    let preview = Crawler::new(LocalFetcher::dir(pages.root()), vec![])
+        .preview(&selector_tree)
+        .await
+        .unwrap();
+    assert_eq!(preview.pages, vec!["http://url-b.com/1"]);
+    assert_eq!(preview.missing_pages, vec!["http://url-a.com/1"]);
+    assert!(render_matches(&selector_tree, &preview, false).ends_with(
+        "1 linked pages were not saved
+  - http://url-a.com/1"
+    ));
+
+    // the start url not saved fails the crawl with the paths looked for
+    let pages = SavedPages::new("empty", &[]);
+    let err = Crawler::new(LocalFetcher::dir(pages.root()), vec![])
+        .preview(&selector_tree)
+        .await
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with("page not saved: http://url-root.com/article/ (looked for "));
+}
+
+#[tokio::test]
+async fn local_fetcher_file_test() {
+    let pages = SavedPages::new(
+        "file",
+        &[(
+            "saved.html",
+            r#"<a class="url" href="http://url-a.com/1">a</a>"#,
+        )],
+    );
+    let selector_tree = selector_tree();
+    let fetcher = LocalFetcher::file(&selector_tree.start_url, pages.root().join("saved.html"));
+    // links can not be followed from a single file
+    let preview = Crawler::new(fetcher, vec![])
+        .with_max_depth(0)
+        .preview(&selector_tree)
+        .await
+        .unwrap();
+    assert_eq!(preview.stats["link"].matched, 1);
+    assert_eq!(preview.skipped_links, 1);
+    assert!(!preview.stats.contains_key("title"));
+}
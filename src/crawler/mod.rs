@@ -1,17 +1,33 @@
 mod formatter;
+mod local;
+mod preview;
+mod record;
 mod selector_node;
 #[cfg(test)]
 mod test;
-
-pub use formatter::format;
-pub use selector_node::{SelectorNode, SelectorTree, SelectorType};
-
-use anyhow::Result;
+mod transform;
+mod value;
+
+pub use formatter::{
+    format, format_each, format_nested, format_with_policy, Column, ColumnPolicy, DroppedRow,
+    FormatError, Formatted, MultiValue,
+};
+pub use local::{LocalFetcher, PageNotSaved};
+pub use preview::{render_matches, render_report, render_tree, Preview, SelectorStats};
+pub use record::Record;
+pub use selector_node::{FieldMapping, SelectorNode, SelectorTree, SelectorType};
+pub use transform::Transform;
+pub use value::{Value, ValueType};
+
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
+use log;
 use reqwest;
 use reqwest::{Client, Url};
 use scraper::{Html, Selector};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
+use std::error;
+use std::fmt;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
@@ -57,13 +73,38 @@ impl FetchClient for WebFetcher {
 #[derive(Debug, PartialEq)]
 pub struct Artifact {
     pub tag: String,
-    pub data: Option<Arc<String>>,
+    pub data: Option<Arc<Value>>,
     pub children: Vec<Artifact>,
 }
 
+// an error of Crawler::crawl(), with the pages fetched until it failed
+#[derive(Debug)]
+pub struct CrawlError {
+    pub pages: usize,
+    pub error: anyhow::Error,
+}
+
+impl fmt::Display for CrawlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.error, f)
+    }
+}
+
+impl error::Error for CrawlError {}
+
 pub struct Crawler<F: FetchClient> {
     fetcher: F,
     skip_urls: HashSet<String>,
+    // pages fetched including the start url. links are not followed beyond it
+    max_pages: Option<usize>,
+    // links followed from the start url in a row. the start url is at depth 0
+    max_depth: Option<usize>,
+    pages: usize,
+    depth: usize,
+    skipped_links: usize,
+    // links to the pages LocalFetcher does not have
+    missing_pages: Vec<String>,
+    stats: BTreeMap<String, SelectorStats>,
 }
 
 impl<F: FetchClient> Crawler<F> {
@@ -73,34 +114,108 @@ impl<F: FetchClient> Crawler<F> {
             skip_urls.insert(skip_url);
         }
 
-        Crawler { fetcher, skip_urls }
+        Crawler {
+            fetcher,
+            skip_urls,
+            max_pages: None,
+            max_depth: None,
+            pages: 0,
+            depth: 0,
+            skipped_links: 0,
+            missing_pages: vec![],
+            stats: BTreeMap::new(),
+        }
+    }
+
+    // the start url is always fetched, so at least 1
+    pub fn with_max_pages(mut self, max_pages: usize) -> Self {
+        self.max_pages = Some(max_pages.max(1));
+        self
+    }
+
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    fn within_limits(&self) -> bool {
+        self.max_pages
+            .is_none_or(|max_pages| self.pages < max_pages)
+            && self
+                .max_depth
+                .is_none_or(|max_depth| self.depth < max_depth)
+    }
+
+    // matched is the number of the elements before `multiple: false` keeps the first
+    fn record(&mut self, node: &SelectorNode, matched: usize) {
+        let stats = self.stats.entry(node.id.clone()).or_default();
+        stats.applied += 1;
+        if matched > 0 {
+            stats.hits += 1;
+        }
+        stats.matched += matched;
+    }
+
+    // elements of a link or an image selector are skipped if they have no href or src
+    fn record_without_attr(&mut self, node: &SelectorNode, without_attr: usize) {
+        if without_attr > 0 {
+            self.stats.entry(node.id.clone()).or_default().without_attr += without_attr;
+        }
     }
 }
 
 impl<F: 'static + FetchClient + Send> Crawler<F> {
+    // returns the artifacts, the access logs and the number of pages fetched including the start url
     pub async fn crawl(
         mut self,
         selector_tree: &SelectorTree,
-    ) -> Result<(Vec<Artifact>, Vec<String>)> {
+    ) -> Result<(Vec<Artifact>, Vec<String>, usize), CrawlError> {
+        match self.track_tree(selector_tree).await {
+            Ok(artifacts) => Ok((artifacts, self.fetcher.dump_access_logs(), self.pages)),
+            Err(error) => Err(CrawlError {
+                pages: self.pages,
+                error,
+            }),
+        }
+    }
+
+    // crawls like crawl(), and reports how the selectors matched
+    pub async fn preview(mut self, selector_tree: &SelectorTree) -> Result<Preview> {
+        let artifacts = self.track_tree(selector_tree).await?;
+        Ok(Preview {
+            artifacts,
+            stats: self.stats,
+            skipped_links: self.skipped_links,
+            missing_pages: self.missing_pages,
+            pages: self.fetcher.dump_access_logs(),
+        })
+    }
+
+    async fn track_tree(&mut self, selector_tree: &SelectorTree) -> Result<Vec<Artifact>> {
+        // a broken selector fails before any page is fetched
+        selector_tree.validate()?;
         let doc = self.fetcher.fetch(&selector_tree.start_url, false).await?;
+        self.pages += 1;
         let children = self
-            .track_nodes(&selector_tree.selectors, &doc.clone())
+            .track_nodes(
+                &selector_tree.selectors,
+                &doc.clone(),
+                &selector_tree.start_url,
+            )
             .await?;
 
-        Ok((
-            vec![Artifact {
-                tag: "source_url".to_string(),
-                data: Some(Arc::new(selector_tree.start_url.clone())),
-                children,
-            }],
-            self.fetcher.dump_access_logs(),
-        ))
+        Ok(vec![Artifact {
+            tag: "source_url".to_string(),
+            data: Some(Arc::new(selector_tree.start_url.clone().into())),
+            children,
+        }])
     }
 
     async fn track_nodes(
         &mut self,
         nodes: &Vec<SelectorNode>,
         doc: &String,
+        url: &str,
     ) -> Result<Vec<Artifact>> {
         let mut artifacts: Vec<Artifact> = vec![];
         for node in nodes {
@@ -110,26 +225,38 @@ impl<F: 'static + FetchClient + Send> Crawler<F> {
                     artifacts.append(&mut children);
                 }
                 SelectorType::Text => {
-                    let data = Self::track_text_node(&node, &doc)?;
+                    let (text, matched) = Self::track_text_node(node, doc)?;
+                    self.record(node, matched);
+                    // the field is treated as missing if the text can't be parsed
+                    let data = match node.value_type.parse(&text, url) {
+                        Ok(value) => Some(Arc::new(value)),
+                        Err(err) => {
+                            log::warn!("failed to parse '{}' at {}: {}", node.id, url, err);
+                            None
+                        }
+                    };
                     artifacts.push(Artifact {
                         tag: node.id.clone(),
-                        data: Some(Arc::new(data)),
+                        data,
                         children: vec![],
                     })
                 }
                 SelectorType::Image => {
-                    let mut image_urls_artifacts = Self::track_image_node(&node, &doc)?
+                    let (image_urls, matched, without_attr) = Self::track_image_node(node, doc)?;
+                    self.record(node, matched);
+                    self.record_without_attr(node, without_attr);
+                    let mut image_urls_artifacts = image_urls
                         .iter()
                         .map(|image_url| Artifact {
                             tag: node.id.clone(),
-                            data: Some(Arc::new(image_url.clone())),
+                            data: Some(Arc::new(image_url.clone().into())),
                             children: vec![],
                         })
                         .collect::<Vec<Artifact>>();
                     artifacts.append(&mut image_urls_artifacts);
                 }
                 SelectorType::Element => {
-                    artifacts.append(&mut self.track_element_node(&node, &doc).await?)
+                    artifacts.append(&mut self.track_element_node(node, doc, url).await?)
                 }
             };
         }
@@ -142,8 +269,9 @@ impl<F: 'static + FetchClient + Send> Crawler<F> {
         &'a mut self,
         node: SelectorNode,
         doc: String,
+        url: String,
     ) -> Pin<Box<(dyn Future<Output = Result<Vec<Artifact>>> + 'a + Send)>> {
-        Box::pin(async move { Ok(self.track_nodes(&node.children, &doc).await?) })
+        Box::pin(async move { self.track_nodes(&node.children, &doc, &url).await })
     }
 
     async fn track_link_node(
@@ -155,11 +283,15 @@ impl<F: 'static + FetchClient + Send> Crawler<F> {
         // needs to drop html_doc(!Send) before async call
         {
             let html_doc = Html::parse_document(doc);
-            let selector = Selector::parse(&node.selector).unwrap();
-            urls = html_doc
-                .select(&selector)
-                .map(|element| Arc::new(element.value().attr("href").unwrap().to_string()))
+            let selector = parse_selector(node)?;
+            let elements = html_doc.select(&selector).collect::<Vec<_>>();
+            urls = elements
+                .iter()
+                .filter_map(|element| element.value().attr("href"))
+                .map(|href| Arc::new(transform::apply_all(&node.transforms, href.to_string())))
                 .collect::<Vec<Arc<String>>>();
+            self.record(node, urls.len());
+            self.record_without_attr(node, elements.len() - urls.len());
             if !node.multiple {
                 urls.truncate(1);
             }
@@ -170,11 +302,28 @@ impl<F: 'static + FetchClient + Send> Crawler<F> {
             if self.skip_urls.contains(&*url) {
                 continue;
             }
-            let html_doc = self.fetcher.fetch(&url, true).await?;
-            let children = self.helper_for_track_nodes(node.clone(), html_doc).await?;
+            if !self.within_limits() {
+                self.skipped_links += 1;
+                continue;
+            }
+            let html_doc = match self.fetcher.fetch(&url, true).await {
+                Ok(html_doc) => html_doc,
+                Err(err) if err.is::<PageNotSaved>() => {
+                    self.missing_pages.push((*url).clone());
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+            self.pages += 1;
+            self.depth += 1;
+            let children = self
+                .helper_for_track_nodes(node.clone(), html_doc, (*url).clone())
+                .await;
+            self.depth -= 1;
+            let children = children?;
             artifacts.push(Artifact {
                 tag: node.id.clone(),
-                data: Some(url.clone()),
+                data: Some(Arc::new((*url).clone().into())),
                 children,
             });
         }
@@ -182,9 +331,10 @@ impl<F: 'static + FetchClient + Send> Crawler<F> {
         Ok(artifacts)
     }
 
-    fn track_text_node(node: &SelectorNode, doc: &String) -> Result<String> {
+    // the text and the number of the matched elements
+    fn track_text_node(node: &SelectorNode, doc: &str) -> Result<(String, usize)> {
         let doc = Html::parse_document(doc);
-        let selector = Selector::parse(&node.selector).unwrap();
+        let selector = parse_selector(node)?;
 
         let mut texts = doc
             .select(&selector)
@@ -196,45 +346,57 @@ impl<F: 'static + FetchClient + Send> Crawler<F> {
                     .join(" ")
             })
             .collect::<Vec<String>>();
+        let matched = texts.len();
 
         if !node.multiple {
             texts.truncate(1);
         }
 
-        Ok(texts.join(" "))
+        Ok((
+            transform::apply_all(&node.transforms, texts.join(" ")),
+            matched,
+        ))
     }
 
-    fn track_image_node(node: &SelectorNode, doc: &String) -> Result<Vec<String>> {
+    // the image urls and the number of the matched elements
+    // returns the urls, the number of them before truncated, and the elements without src
+    fn track_image_node(node: &SelectorNode, doc: &str) -> Result<(Vec<String>, usize, usize)> {
         let doc = Html::parse_document(doc);
-        let selector = Selector::parse(&node.selector).unwrap();
+        let selector = parse_selector(node)?;
 
-        let mut image_urls = doc
-            .select(&selector)
-            .map(|element| element.value().attr("src").unwrap().to_string())
+        let elements = doc.select(&selector).collect::<Vec<_>>();
+        let mut image_urls = elements
+            .iter()
+            .filter_map(|element| element.value().attr("src"))
+            .map(|src| transform::apply_all(&node.transforms, src.to_string()))
             .collect::<Vec<String>>();
+        let matched = image_urls.len();
+        let without_attr = elements.len() - matched;
 
         if !node.multiple {
             image_urls.truncate(1);
         }
 
-        Ok(image_urls)
+        Ok((image_urls, matched, without_attr))
     }
 
     async fn track_element_node(
         &mut self,
         node: &SelectorNode,
         doc: &String,
+        url: &str,
     ) -> Result<Vec<Artifact>> {
         let mut selected_docs: Vec<String>;
         // needs to drop html_doc(!Send) before async call
         {
             let html_doc = Html::parse_document(doc);
-            let selector = Selector::parse(&node.selector).unwrap();
+            let selector = parse_selector(node)?;
 
             selected_docs = html_doc
                 .select(&selector)
                 .map(|element| element.inner_html())
                 .collect::<Vec<String>>();
+            self.record(node, selected_docs.len());
 
             if !node.multiple {
                 selected_docs.truncate(1);
@@ -247,7 +409,7 @@ impl<F: 'static + FetchClient + Send> Crawler<F> {
                 tag: node.id.clone(),
                 data: None,
                 children: self
-                    .helper_for_track_nodes(node.clone(), selected_doc)
+                    .helper_for_track_nodes(node.clone(), selected_doc, url.to_string())
                     .await?,
             })
         }
@@ -255,3 +417,23 @@ impl<F: 'static + FetchClient + Send> Crawler<F> {
         Ok(artifacts)
     }
 }
+
+// the CSS selector of the node, or an error naming the selector
+fn parse_selector(node: &SelectorNode) -> Result<Selector> {
+    Selector::parse(&node.selector).map_err(|err| {
+        anyhow!(
+            "invalid selector '{}' of '{}': {:?}",
+            node.selector,
+            node.id,
+            err.kind
+        )
+    })
+}
+
+pub(crate) fn validate_selectors(nodes: &[SelectorNode]) -> Result<()> {
+    for node in nodes {
+        parse_selector(node)?;
+        validate_selectors(&node.children)?;
+    }
+    Ok(())
+}
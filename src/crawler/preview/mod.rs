@@ -0,0 +1,196 @@
+#[cfg(test)]
+mod test;
+
+use crate::crawler::{Artifact, SelectorNode, SelectorTree, SelectorType};
+use std::collections::BTreeMap;
+
+// values longer than this are cut in the artifact tree
+const MAX_VALUE_CHARS: usize = 80;
+// values shown per selector by render_matches()
+const MAX_VALUES: usize = 20;
+
+// ANSI escapes to highlight the selectors which matched nothing
+const HIGHLIGHT_START: &str = "\x1b[1;31m";
+const HIGHLIGHT_END: &str = "\x1b[0m";
+
+// result of Crawler::preview()
+#[derive(Debug)]
+pub struct Preview {
+    pub artifacts: Vec<Artifact>,
+    // selector id -> how it matched
+    pub stats: BTreeMap<String, SelectorStats>,
+    // links not followed because of max_pages or max_depth
+    pub skipped_links: usize,
+    // linked pages which were not saved for LocalFetcher
+    pub missing_pages: Vec<String>,
+    // urls fetched by following links. the start url is not included
+    pub pages: Vec<String>,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct SelectorStats {
+    // documents the selector was applied to. a page, or an element of the parent selector
+    pub applied: usize,
+    // documents where the selector matched at least one element
+    pub hits: usize,
+    // elements matched in all documents
+    pub matched: usize,
+    // elements matched but skipped for lacking href of a link or src of an image
+    pub without_attr: usize,
+}
+
+// an artifact per line, indented by the depth
+// ex:
+// source_url: https://formula1-data.com/article
+//   link: https://formula1-data.com/article/1
+//     title: Hamilton wins
+pub fn render_tree(artifacts: &[Artifact]) -> String {
+    let mut lines = vec![];
+    for artifact in artifacts {
+        render_artifact(artifact, 0, &mut lines);
+    }
+    lines.join("\n")
+}
+
+fn render_artifact(artifact: &Artifact, depth: usize, lines: &mut Vec<String>) {
+    let indent = "  ".repeat(depth);
+    lines.push(match &artifact.data {
+        Some(data) => format!("{}{}: {}", indent, artifact.tag, shorten(&data.to_string())),
+        None => format!("{}{}", indent, artifact.tag),
+    });
+    for child in &artifact.children {
+        render_artifact(child, depth + 1, lines);
+    }
+}
+
+// a line, cut to MAX_VALUE_CHARS characters. an empty value is shown as ""
+fn shorten(value: &str) -> String {
+    let value = value.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if value.is_empty() {
+        return "\"\"".to_string();
+    }
+    if value.chars().count() <= MAX_VALUE_CHARS {
+        return value;
+    }
+    let cut = value.chars().take(MAX_VALUE_CHARS).collect::<String>();
+    format!("{}…", cut)
+}
+
+// a selector per line in the order of the sitemap, indented by the depth
+// the selectors which matched nothing are marked, and highlighted if highlight is true
+pub fn render_report(selector_tree: &SelectorTree, preview: &Preview, highlight: bool) -> String {
+    let mut lines = vec![];
+    for node in &selector_tree.selectors {
+        render_selector(node, 0, preview, highlight, None, &mut lines);
+    }
+    render_skipped(preview, &mut lines);
+    lines.join("\n")
+}
+
+// render_report() followed by the values each selector extracted
+// ex:
+// link (Link ".url"): 2 matches in 1 of 1 documents
+//   - https://formula1-data.com/article/1
+//   title (Text ".title"): 1 matches in 1 of 1 documents
+//     - Hamilton wins
+pub fn render_matches(selector_tree: &SelectorTree, preview: &Preview, highlight: bool) -> String {
+    let mut values = BTreeMap::new();
+    for artifact in &preview.artifacts {
+        collect_values(artifact, &mut values);
+    }
+    let mut lines = vec![];
+    for node in &selector_tree.selectors {
+        render_selector(node, 0, preview, highlight, Some(&values), &mut lines);
+    }
+    render_skipped(preview, &mut lines);
+    lines.join("\n")
+}
+
+// the links which were not followed, and the linked pages which were not saved
+fn render_skipped(preview: &Preview, lines: &mut Vec<String>) {
+    if preview.skipped_links > 0 {
+        lines.push(format!(
+            "{} links were not followed because of the limits",
+            preview.skipped_links
+        ));
+    }
+    if !preview.missing_pages.is_empty() {
+        lines.push(format!(
+            "{} linked pages were not saved",
+            preview.missing_pages.len()
+        ));
+        for url in preview.missing_pages.iter().take(MAX_VALUES) {
+            lines.push(format!("  - {}", url));
+        }
+        if preview.missing_pages.len() > MAX_VALUES {
+            lines.push(format!(
+                "  … {} more",
+                preview.missing_pages.len() - MAX_VALUES
+            ));
+        }
+    }
+}
+
+// tag -> values of the artifacts. elements have no value
+fn collect_values(artifact: &Artifact, values: &mut BTreeMap<String, Vec<String>>) {
+    if let Some(data) = &artifact.data {
+        values
+            .entry(artifact.tag.clone())
+            .or_default()
+            .push(shorten(&data.to_string()));
+    }
+    for child in &artifact.children {
+        collect_values(child, values);
+    }
+}
+
+fn render_selector(
+    node: &SelectorNode,
+    depth: usize,
+    preview: &Preview,
+    highlight: bool,
+    values: Option<&BTreeMap<String, Vec<String>>>,
+    lines: &mut Vec<String>,
+) {
+    let stats = preview.stats.get(&node.id).copied().unwrap_or_default();
+    let label = format!(
+        "{} ({:?} \"{}\")",
+        node.id, node.selector_type, node.selector
+    );
+    let line = if stats.applied == 0 {
+        format!("{}: not reached", label)
+    } else if stats.matched == 0 {
+        let line = format!("{}: matched nothing in {} documents", label, stats.applied);
+        if highlight {
+            format!("{}{}{}", HIGHLIGHT_START, line, HIGHLIGHT_END)
+        } else {
+            line
+        }
+    } else {
+        format!(
+            "{}: {} matches in {} of {} documents",
+            label, stats.matched, stats.hits, stats.applied
+        )
+    };
+    let line = match (stats.without_attr, &node.selector_type) {
+        (0, _) => line,
+        (without_attr, SelectorType::Link) => format!("{}, {} without href", line, without_attr),
+        (without_attr, _) => format!("{}, {} without src", line, without_attr),
+    };
+    lines.push(format!("{}{}", "  ".repeat(depth), line));
+    if let Some(node_values) = values.and_then(|values| values.get(&node.id)) {
+        for value in node_values.iter().take(MAX_VALUES) {
+            lines.push(format!("{}- {}", "  ".repeat(depth + 1), value));
+        }
+        if node_values.len() > MAX_VALUES {
+            lines.push(format!(
+                "{}… {} more",
+                "  ".repeat(depth + 1),
+                node_values.len() - MAX_VALUES
+            ));
+        }
+    }
+    for child in &node.children {
+        render_selector(child, depth + 1, preview, highlight, values, lines);
+    }
+}
@@ -0,0 +1,232 @@
+use crate::crawler::{
+    render_report, render_tree, Crawler, FetchClient, SelectorStats, SelectorTree,
+};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use std::collections::HashMap;
+
+// mapping Url -> Html
+struct MockedFetcher {
+    mapping: HashMap<String, String>,
+    access_logs: Vec<String>,
+}
+
+impl MockedFetcher {
+    fn new() -> Self {
+        let pages = vec![
+            (
+                "http://url-root.com/article",
+                r#"<a class="url" href="http://url-a.com">a</a>
+                   <a class="url" href="http://url-b.com">b</a>
+                   <a class="url" href="http://url-c.com">c</a>"#,
+            ),
+            (
+                "http://url-a.com",
+                r#"<p class="title">title A</p><a class="next" href="http://url-a.com/2">next</a>"#,
+            ),
+            (
+                "http://url-b.com",
+                r#"<p class="title">title B</p><a class="next" href="http://url-b.com/2">next</a>"#,
+            ),
+            (
+                "http://url-c.com",
+                r#"<p class="title">title C</p><a class="next" href="http://url-c.com/2">next</a>"#,
+            ),
+            ("http://url-a.com/2", r#"<p class="title">title A2</p>"#),
+            ("http://url-b.com/2", r#"<p class="title">title B2</p>"#),
+            ("http://url-c.com/2", r#"<p class="title">title C2</p>"#),
+        ];
+        MockedFetcher {
+            mapping: pages
+                .into_iter()
+                .map(|(url, html)| (url.to_string(), html.to_string()))
+                .collect(),
+            access_logs: vec![],
+        }
+    }
+}
+
+#[async_trait]
+impl FetchClient for MockedFetcher {
+    async fn fetch(&mut self, url: &String, logging: bool) -> Result<String> {
+        let html = self
+            .mapping
+            .get(url)
+            .cloned()
+            .ok_or_else(|| anyhow!("html not found by the url: {}", url))?;
+        if logging {
+            self.access_logs.push(url.clone());
+        }
+        Ok(html)
+    }
+
+    fn dump_access_logs(self) -> Vec<String> {
+        self.access_logs
+    }
+}
+
+fn selector_tree() -> SelectorTree {
+    SelectorTree::new(
+        r#"{
+  "_id": "test",
+  "startUrl": ["http://url-root.com/article"],
+  "selectors": [
+    {"id": "link", "type": "SelectorLink", "parentSelectors": ["_root"], "selector": ".url", "multiple": true, "delay": 0},
+    {"id": "title", "type": "SelectorText", "parentSelectors": ["link"], "selector": ".title", "multiple": false, "regex": "", "delay": 0},
+    {"id": "author", "type": "SelectorText", "parentSelectors": ["link"], "selector": ".author", "multiple": false, "regex": "", "delay": 0},
+    {"id": "next", "type": "SelectorLink", "parentSelectors": ["link"], "selector": ".next", "multiple": false, "delay": 0},
+    {"id": "next_title", "type": "SelectorText", "parentSelectors": ["next"], "selector": ".title", "multiple": false, "regex": "", "delay": 0}
+  ]
+}"#
+        .to_string(),
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn preview_test() {
+    let preview = Crawler::new(MockedFetcher::new(), vec![])
+        .preview(&selector_tree())
+        .await
+        .unwrap();
+    assert_eq!(preview.pages.len(), 6);
+    assert_eq!(preview.skipped_links, 0);
+    assert_eq!(
+        preview.stats["link"],
+        SelectorStats {
+            applied: 1,
+            hits: 1,
+            matched: 3,
+            without_attr: 0,
+        }
+    );
+    assert_eq!(
+        preview.stats["author"],
+        SelectorStats {
+            applied: 3,
+            hits: 0,
+            matched: 0,
+            without_attr: 0,
+        }
+    );
+    assert_eq!(preview.stats["next_title"].matched, 3);
+}
+
+#[tokio::test]
+async fn preview_invalid_selector_test() {
+    let selector_tree = SelectorTree::new(
+        r#"{
+  "_id": "test",
+  "startUrl": ["http://url-root.com/article"],
+  "selectors": [
+    {"id": "link", "type": "SelectorLink", "parentSelectors": ["_root"], "selector": ".url", "multiple": true, "delay": 0},
+    {"id": "title", "type": "SelectorText", "parentSelectors": ["link"], "selector": ".title[", "multiple": false, "regex": "", "delay": 0}
+  ]
+}"#
+        .to_string(),
+    )
+    .unwrap();
+    let fetcher = MockedFetcher::new();
+    // fails before fetching, naming the selector
+    let err = Crawler::new(fetcher, vec![])
+        .preview(&selector_tree)
+        .await
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with("invalid selector '.title[' of 'title': "));
+}
+
+#[tokio::test]
+async fn preview_limits_test() {
+    // the links of the start url only
+    let preview = Crawler::new(MockedFetcher::new(), vec![])
+        .with_max_depth(1)
+        .preview(&selector_tree())
+        .await
+        .unwrap();
+    assert_eq!(
+        preview.pages,
+        vec!["http://url-a.com", "http://url-b.com", "http://url-c.com"]
+    );
+    assert_eq!(preview.skipped_links, 3);
+    assert!(!preview.stats.contains_key("next_title"));
+
+    // the start url and 2 more pages
+    let preview = Crawler::new(MockedFetcher::new(), vec![])
+        .with_max_pages(3)
+        .preview(&selector_tree())
+        .await
+        .unwrap();
+    assert_eq!(
+        preview.pages,
+        vec!["http://url-a.com", "http://url-a.com/2"]
+    );
+    assert_eq!(preview.skipped_links, 2);
+
+    let selector_tree = selector_tree();
+    let preview = Crawler::new(MockedFetcher::new(), vec![])
+        .with_max_pages(2)
+        .preview(&selector_tree)
+        .await
+        .unwrap();
+    assert_eq!(
+        render_tree(&preview.artifacts),
+        "source_url: http://url-root.com/article
+  link: http://url-a.com
+    title: title A
+    author: \"\""
+    );
+    assert_eq!(
+        render_report(&selector_tree, &preview, false),
+        r#"link (Link ".url"): 3 matches in 1 of 1 documents
+  title (Text ".title"): 1 matches in 1 of 1 documents
+  author (Text ".author"): matched nothing in 1 documents
+  next (Link ".next"): 1 matches in 1 of 1 documents
+    next_title (Text ".title"): not reached
+3 links were not followed because of the limits"#
+    );
+    assert!(render_report(&selector_tree, &preview, true)
+        .contains("\x1b[1;31mauthor (Text \".author\"): matched nothing in 1 documents\x1b[0m"));
+}
+
+#[tokio::test]
+async fn preview_without_attr_test() {
+    let mut fetcher = MockedFetcher::new();
+    fetcher.mapping.insert(
+        "http://url-root.com/article".to_string(),
+        r#"<a name="top"></a><a href="http://url-a.com">a</a><img><img src="a.png">"#.to_string(),
+    );
+    let selector_tree = SelectorTree::new(
+        r#"{
+  "_id": "test",
+  "startUrl": ["http://url-root.com/article"],
+  "selectors": [
+    {"id": "link", "type": "SelectorLink", "parentSelectors": ["_root"], "selector": "a", "multiple": true, "delay": 0},
+    {"id": "image", "type": "SelectorImage", "parentSelectors": ["_root"], "selector": "img", "multiple": true, "delay": 0}
+  ]
+}"#
+        .to_string(),
+    )
+    .unwrap();
+    // the elements without href or src are skipped and counted
+    let preview = Crawler::new(fetcher, vec![])
+        .preview(&selector_tree)
+        .await
+        .unwrap();
+    assert_eq!(preview.pages, vec!["http://url-a.com"]);
+    assert_eq!(
+        preview.stats["image"],
+        SelectorStats {
+            applied: 1,
+            hits: 1,
+            matched: 1,
+            without_attr: 1,
+        }
+    );
+    assert_eq!(
+        render_report(&selector_tree, &preview, false),
+        r#"link (Link "a"): 1 matches in 1 of 1 documents, 1 without href
+image (Image "img"): 1 matches in 1 of 1 documents, 1 without src"#
+    );
+}
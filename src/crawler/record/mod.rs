@@ -0,0 +1,68 @@
+#[cfg(test)]
+mod test;
+
+use crate::crawler::Value;
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+use serde_json;
+use std::sync::Arc;
+
+// a formatted row, ordered by the columns given to format()
+#[derive(Clone, PartialEq, Debug)]
+pub struct Record {
+    // shared by all records of a format() call
+    columns: Arc<Vec<String>>,
+    values: Vec<Option<Arc<Value>>>,
+}
+
+impl Record {
+    // panics if the number of values differs from the columns
+    pub fn new(columns: Arc<Vec<String>>, values: Vec<Option<Arc<Value>>>) -> Self {
+        assert_eq!(
+            columns.len(),
+            values.len(),
+            "columns and values differ in length"
+        );
+        Record { columns, values }
+    }
+
+    // None if the column does not exist or its value is missing
+    pub fn get(&self, column: &str) -> Option<&Value> {
+        self.columns
+            .iter()
+            .position(|c| c == column)
+            .and_then(|i| self.values[i].as_deref())
+    }
+
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    pub fn values(&self) -> &[Option<Arc<Value>>] {
+        &self.values
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Option<&Value>)> {
+        self.columns
+            .iter()
+            .map(String::as_str)
+            .zip(self.values.iter().map(Option::as_deref))
+    }
+
+    // ex: record.deserialize::<Article>()
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T> {
+        Ok(serde_json::from_value(serde_json::to_value(self)?)?)
+    }
+}
+
+impl Serialize for Record {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.columns.len()))?;
+        for (column, value) in self.iter() {
+            map.serialize_entry(column, &value)?;
+        }
+        map.end()
+    }
+}
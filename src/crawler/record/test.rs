@@ -0,0 +1,71 @@
+use crate::crawler::record::Record;
+use crate::crawler::Value;
+use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+use serde::Deserialize;
+use std::sync::Arc;
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Article {
+    title: String,
+    laps: i64,
+    published_at: DateTime<Utc>,
+    subtitle: Option<String>,
+}
+
+fn record() -> Record {
+    Record::new(
+        Arc::new(vec![
+            "title".into(),
+            "laps".into(),
+            "published_at".into(),
+            "subtitle".into(),
+        ]),
+        vec![
+            Some(Arc::new("title A".into())),
+            Some(Arc::new(Value::Integer(16845))),
+            Some(Arc::new(Value::DateTime(
+                FixedOffset::east_opt(9 * 3600)
+                    .unwrap()
+                    .with_ymd_and_hms(2020, 8, 10, 21, 5, 0)
+                    .unwrap(),
+            ))),
+            None,
+        ],
+    )
+}
+
+#[test]
+fn record_get_test() {
+    let record = record();
+
+    assert_eq!(Some(&Value::from("title A")), record.get("title"));
+    assert_eq!(Some(&Value::Integer(16845)), record.get("laps"));
+    assert_eq!(None, record.get("subtitle"));
+    assert_eq!(None, record.get("unknown"));
+    assert_eq!(
+        vec!["title", "laps", "published_at", "subtitle"],
+        record
+            .iter()
+            .map(|(column, _)| column)
+            .collect::<Vec<&str>>()
+    );
+}
+
+#[test]
+fn record_serde_test() {
+    let record = record();
+
+    assert_eq!(
+        r#"{"title":"title A","laps":16845,"published_at":"2020-08-10T21:05:00+09:00","subtitle":null}"#,
+        serde_json::to_string(&record).unwrap()
+    );
+    assert_eq!(
+        Article {
+            title: "title A".into(),
+            laps: 16845,
+            published_at: Utc.with_ymd_and_hms(2020, 8, 10, 12, 5, 0).unwrap(),
+            subtitle: None,
+        },
+        record.deserialize::<Article>().unwrap()
+    );
+}
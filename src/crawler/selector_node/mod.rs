@@ -1,71 +1,158 @@
 #[cfg(test)]
 mod test;
 
+use crate::crawler::formatter::{Column, ColumnPolicy, MultiValue};
+use crate::crawler::transform::Transform;
+use crate::crawler::value::ValueType;
+use anyhow::{anyhow, Error, Result};
 use serde::{Deserialize, Serialize};
 use serde_json;
+use std::convert::TryFrom;
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct SelectorTree {
     pub _id: String,
     pub start_url: String,
     pub selectors: Vec<SelectorNode>,
+    #[serde(default)]
+    pub fields: Vec<FieldMapping>,
 }
 
 impl SelectorTree {
-    pub fn new(sitemap_json: String) -> Result<Self, serde_json::Error> {
+    pub fn new(sitemap_json: String) -> Result<Self> {
         let sitemap = SiteMap::new(sitemap_json)?;
+        let start_url = sitemap
+            .start_url
+            .first()
+            .ok_or_else(|| anyhow!("startUrl of '{}' is empty", sitemap._id))?
+            .clone();
         Ok(SelectorTree {
             _id: sitemap._id.clone(),
-            start_url: sitemap.start_url[0].clone(),
-            selectors: SelectorNode::new(sitemap),
+            start_url,
+            fields: sitemap.fields.clone(),
+            selectors: SelectorNode::new(sitemap)?,
         })
     }
 
+    // the selectors are parsed only when crawling, so a sitemap is checked before it is stored
+    pub fn validate(&self) -> Result<()> {
+        crate::crawler::validate_selectors(&self.selectors)
+    }
+
     pub fn from_json(json: String) -> Result<Self, serde_json::Error> {
         serde_json::from_str(&json)
     }
 }
 
+// maps a selector id (column) to a field of Content
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(from = "RawFieldMapping")]
+pub struct FieldMapping {
+    pub column: String,
+    pub field: String,
+    #[serde(default)]
+    pub policy: ColumnPolicy,
+    #[serde(default)]
+    pub multi: MultiValue,
+}
+
+impl FieldMapping {
+    pub fn new(column: &str, field: &str, policy: ColumnPolicy) -> Self {
+        FieldMapping {
+            column: column.to_string(),
+            field: field.to_string(),
+            policy,
+            multi: MultiValue::default(),
+        }
+    }
+
+    pub fn to_column(&self) -> Column {
+        Column {
+            multi: self.multi.clone(),
+            ..Column::new(&self.column, self.policy.clone())
+        }
+    }
+}
+
+// FieldMapping as written in a sitemap. the keys required and default are read for the sitemaps
+// written before policy, and ignored if policy is given
+#[derive(Deserialize)]
+struct RawFieldMapping {
+    column: String,
+    field: String,
+    policy: Option<ColumnPolicy>,
+    required: Option<bool>,
+    default: Option<String>,
+    #[serde(default)]
+    multi: MultiValue,
+}
+
+impl From<RawFieldMapping> for FieldMapping {
+    fn from(raw: RawFieldMapping) -> Self {
+        let policy = match (raw.policy, raw.default, raw.required) {
+            (Some(policy), _, _) => policy,
+            (None, Some(default), _) => ColumnPolicy::Default(default),
+            (None, None, Some(false)) => ColumnPolicy::Optional,
+            (None, None, _) => ColumnPolicy::Required,
+        };
+        FieldMapping {
+            column: raw.column,
+            field: raw.field,
+            policy,
+            multi: raw.multi,
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct SelectorNode {
     pub id: String,
     pub selector_type: SelectorType,
     pub selector: String,
     pub multiple: bool,
+    #[serde(default)]
+    pub value_type: ValueType,
+    #[serde(default)]
+    pub transforms: Vec<Transform>,
     pub children: Vec<SelectorNode>,
 }
 
 impl SelectorNode {
-    fn new(mut sitemap: SiteMap) -> Vec<Self> {
-        build_selector_node(&mut sitemap.selectors, &"_root".into())
+    fn new(sitemap: SiteMap) -> Result<Vec<Self>> {
+        build_selector_node(&sitemap.selectors, &"_root".into())
     }
 
-    fn from_raw(raw: &RawSelector) -> Self {
-        SelectorNode {
+    fn from_raw(raw: &RawSelector) -> Result<Self> {
+        Ok(SelectorNode {
             id: raw.id.clone(),
-            selector_type: SelectorType::from_str(&raw._type),
+            selector_type: SelectorType::try_from(raw._type.as_str())?,
             selector: raw.selector.clone(),
             multiple: raw.multiple,
+            value_type: raw.value_type.clone(),
+            transforms: raw.transforms.clone(),
             children: vec![],
-        }
+        })
     }
 }
 
-fn build_selector_node(raw_selectors: &Vec<RawSelector>, parent_id: &String) -> Vec<SelectorNode> {
+fn build_selector_node(
+    raw_selectors: &Vec<RawSelector>,
+    parent_id: &String,
+) -> Result<Vec<SelectorNode>> {
     let mut children_selectors = vec![];
 
     for raw_selector in raw_selectors {
         if raw_selector.parent_selectors.contains(parent_id) {
-            children_selectors.push(SelectorNode::from_raw(raw_selector));
+            children_selectors.push(SelectorNode::from_raw(raw_selector)?);
         }
     }
 
     for child_selector in &mut children_selectors {
         child_selector
             .children
-            .append(&mut build_selector_node(raw_selectors, &child_selector.id));
+            .append(&mut build_selector_node(raw_selectors, &child_selector.id)?);
     }
-    children_selectors
+    Ok(children_selectors)
 }
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
@@ -76,14 +163,16 @@ pub enum SelectorType {
     Element,
 }
 
-impl SelectorType {
-    fn from_str(s: &str) -> Self {
+impl TryFrom<&str> for SelectorType {
+    type Error = Error;
+
+    fn try_from(s: &str) -> Result<Self> {
         match s {
-            "SelectorText" => SelectorType::Text,
-            "SelectorLink" => SelectorType::Link,
-            "SelectorImage" => SelectorType::Image,
-            "SelectorElement" => SelectorType::Element,
-            _ => panic!("unknown selector type"),
+            "SelectorText" => Ok(SelectorType::Text),
+            "SelectorLink" => Ok(SelectorType::Link),
+            "SelectorImage" => Ok(SelectorType::Image),
+            "SelectorElement" => Ok(SelectorType::Element),
+            _ => Err(anyhow!("unknown selector type: {}", s)),
         }
     }
 }
@@ -94,6 +183,8 @@ struct SiteMap {
     #[serde(rename(deserialize = "startUrl"))]
     start_url: Vec<String>,
     selectors: Vec<RawSelector>,
+    #[serde(default)]
+    fields: Vec<FieldMapping>,
 }
 
 impl SiteMap {
@@ -112,4 +203,8 @@ struct RawSelector {
     #[serde(rename(deserialize = "parentSelectors"))]
     parent_selectors: Vec<String>,
     delay: i32,
+    #[serde(default, rename(deserialize = "valueType"))]
+    value_type: ValueType,
+    #[serde(default)]
+    transforms: Vec<Transform>,
 }
@@ -1,4 +1,8 @@
-use crate::crawler::selector_node::{SelectorNode, SelectorType, SiteMap};
+use crate::crawler::formatter::ColumnPolicy;
+use crate::crawler::selector_node::{
+    FieldMapping, SelectorNode, SelectorTree, SelectorType, SiteMap,
+};
+use crate::crawler::value::ValueType;
 
 #[test]
 fn test_selector_tree_new() {
@@ -50,12 +54,16 @@ fn test_selector_tree_new() {
         selector_type: SelectorType::Link,
         selector: ".mdlGrid__col12 a".into(),
         multiple: true,
+        value_type: ValueType::Text,
+        transforms: vec![],
         children: vec![
             SelectorNode {
                 id: "title".into(),
                 selector_type: SelectorType::Text,
                 selector: "h1.entryHeader__title".into(),
                 multiple: false,
+                value_type: ValueType::Text,
+                transforms: vec![],
                 children: vec![],
             },
             SelectorNode {
@@ -63,13 +71,146 @@ fn test_selector_tree_new() {
                 selector_type: SelectorType::Text,
                 selector: ".entry > p".into(),
                 multiple: true,
+                value_type: ValueType::Text,
+                transforms: vec![],
                 children: vec![],
             },
         ],
     }];
 
     let sitemap = SiteMap::new(testdata.into()).unwrap();
-    let actual = SelectorNode::new(sitemap);
+    let actual = SelectorNode::new(sitemap).unwrap();
 
     assert_eq!(actual, expected)
 }
+
+#[test]
+fn test_selector_tree_fields() {
+    let testdata = r###"
+{
+  "_id": "formula1-data",
+  "startUrl": [
+    "https://formula1-data.com/article"
+  ],
+  "selectors": [],
+  "fields": [
+    {
+      "column": "link",
+      "field": "url"
+    },
+    {
+      "column": "subtitle",
+      "field": "subtitle",
+      "policy": "Optional"
+    },
+    {
+      "column": "category",
+      "field": "category",
+      "policy": {
+        "Default": "news"
+      }
+    }
+  ]
+}
+    "###;
+
+    let expected = vec![
+        FieldMapping::new("link", "url", ColumnPolicy::Required),
+        FieldMapping::new("subtitle", "subtitle", ColumnPolicy::Optional),
+        FieldMapping::new("category", "category", ColumnPolicy::Default("news".into())),
+    ];
+
+    let tree = SelectorTree::new(testdata.into()).unwrap();
+    assert_eq!(tree.fields, expected);
+}
+
+#[test]
+fn test_selector_tree_fields_required_default() {
+    // the keys used before policy
+    let testdata = r###"
+{
+  "_id": "formula1-data",
+  "startUrl": [
+    "https://formula1-data.com/article"
+  ],
+  "selectors": [],
+  "fields": [
+    {
+      "column": "link",
+      "field": "url",
+      "required": true
+    },
+    {
+      "column": "subtitle",
+      "field": "subtitle",
+      "required": false
+    },
+    {
+      "column": "category",
+      "field": "category",
+      "required": false,
+      "default": "news"
+    },
+    {
+      "column": "author",
+      "field": "author",
+      "required": false,
+      "policy": "SkipRow"
+    }
+  ]
+}
+    "###;
+
+    let expected = vec![
+        FieldMapping::new("link", "url", ColumnPolicy::Required),
+        FieldMapping::new("subtitle", "subtitle", ColumnPolicy::Optional),
+        FieldMapping::new("category", "category", ColumnPolicy::Default("news".into())),
+        FieldMapping::new("author", "author", ColumnPolicy::SkipRow),
+    ];
+
+    let tree = SelectorTree::new(testdata.into()).unwrap();
+    assert_eq!(tree.fields, expected);
+    // stored selectors are written with policy
+    let json = serde_json::to_string(&tree).unwrap();
+    assert_eq!(SelectorTree::from_json(json).unwrap(), tree);
+}
+
+#[test]
+fn test_selector_tree_invalid() {
+    let sitemap = |start_url: &str, selector_type: &str, selector: &str| {
+        format!(
+            r#"{{"_id": "formula1-data", "startUrl": {}, "selectors": [{{"id": "title", "type": "{}", "selector": "{}", "multiple": false, "parentSelectors": ["_root"], "delay": 0}}]}}"#,
+            start_url, selector_type, selector
+        )
+    };
+
+    let err = SelectorTree::new(sitemap("[]", "SelectorText", ".title")).unwrap_err();
+    assert_eq!(err.to_string(), "startUrl of 'formula1-data' is empty");
+    let err = SelectorTree::new(sitemap(
+        r#"["https://formula1-data.com"]"#,
+        "SelectorTable",
+        ".title",
+    ))
+    .unwrap_err();
+    assert_eq!(err.to_string(), "unknown selector type: SelectorTable");
+
+    // a broken css selector is found by validate, not by new
+    let tree = SelectorTree::new(sitemap(
+        r#"["https://formula1-data.com"]"#,
+        "SelectorText",
+        ".title[",
+    ))
+    .unwrap();
+    assert!(tree
+        .validate()
+        .unwrap_err()
+        .to_string()
+        .starts_with("invalid selector '.title[' of 'title'"));
+    let tree = SelectorTree::new(sitemap(
+        r#"["https://formula1-data.com"]"#,
+        "SelectorText",
+        ".title",
+    ))
+    .unwrap();
+    assert!(tree.validate().is_ok());
+}
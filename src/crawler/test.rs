@@ -331,7 +331,7 @@ async fn fetcher_crawler_test() {
         let mocked_fetcher = MockedFetcher::new(url_map);
         let executor = Crawler::new(mocked_fetcher, vec![]);
         let selector = SelectorTree::new(selector_json).unwrap();
-        let (actual, _) = executor.crawl(&selector).await.unwrap();
+        let (actual, _, _) = executor.crawl(&selector).await.unwrap();
 
         assert_eq!(expected, actual, "{}", name)
     }
@@ -0,0 +1,55 @@
+#[cfg(test)]
+mod test;
+
+use html_escape::decode_html_entities;
+use serde::{Deserialize, Serialize};
+
+// post-processing applied to an extracted text, in the order of the list
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Transform {
+    // remove leading and trailing whitespace
+    Trim,
+    // turn every run of whitespace into a single space
+    CollapseWhitespace,
+    Replace { from: String, to: String },
+    Prefix(String),
+    Suffix(String),
+    // "&amp;" -> "&"
+    DecodeHtmlEntities,
+    // full-width alphanumerics, symbols and spaces to half-width. ex: "ＡＢＣ１２３" -> "ABC123"
+    NormalizeWidth,
+    // keep at most n characters
+    Truncate(usize),
+}
+
+impl Transform {
+    pub fn apply(&self, text: &str) -> String {
+        match self {
+            Transform::Trim => text.trim().to_string(),
+            Transform::CollapseWhitespace => {
+                text.split_whitespace().collect::<Vec<&str>>().join(" ")
+            }
+            Transform::Replace { from, to } => text.replace(from.as_str(), to),
+            Transform::Prefix(prefix) => format!("{}{}", prefix, text),
+            Transform::Suffix(suffix) => format!("{}{}", text, suffix),
+            Transform::DecodeHtmlEntities => decode_html_entities(text).into_owned(),
+            Transform::NormalizeWidth => text.chars().map(to_half_width).collect(),
+            Transform::Truncate(n) => text.chars().take(*n).collect(),
+        }
+    }
+}
+
+pub fn apply_all(transforms: &[Transform], text: String) -> String {
+    transforms
+        .iter()
+        .fold(text, |text, transform| transform.apply(&text))
+}
+
+fn to_half_width(c: char) -> char {
+    match c {
+        // "！" (U+FF01) .. "～" (U+FF5E) are shifted from "!" .. "~"
+        '\u{FF01}'..='\u{FF5E}' => std::char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+        '\u{3000}' => ' ',
+        _ => c,
+    }
+}
@@ -0,0 +1,59 @@
+use crate::crawler::transform::{apply_all, Transform};
+
+#[test]
+fn transform_apply_test() {
+    let test_data = vec![
+        (Transform::Trim, "  title A \n", "title A"),
+        (
+            Transform::CollapseWhitespace,
+            " body\n\n A1 \t body A2 ",
+            "body A1 body A2",
+        ),
+        (
+            Transform::Replace {
+                from: "F1".into(),
+                to: "Formula 1".into(),
+            },
+            "F1-70周年記念GP / F1",
+            "Formula 1-70周年記念GP / Formula 1",
+        ),
+        (
+            Transform::Prefix("https://example.com".into()),
+            "/a",
+            "https://example.com/a",
+        ),
+        (Transform::Suffix(" km/h".into()), "320", "320 km/h"),
+        (
+            Transform::DecodeHtmlEntities,
+            "Tom &amp; Jerry &lt;3 &#12354;",
+            "Tom & Jerry <3 あ",
+        ),
+        (
+            Transform::NormalizeWidth,
+            "ＡＢＣ　１２３！ ｶﾀｶﾅ ひらがな",
+            "ABC 123! ｶﾀｶﾅ ひらがな",
+        ),
+        (Transform::Truncate(5), "レッドブル・ホンダ", "レッドブル"),
+        (Transform::Truncate(10), "short", "short"),
+    ];
+
+    for (transform, input, expected) in test_data {
+        assert_eq!(expected, transform.apply(input), "{:?}", transform)
+    }
+}
+
+#[test]
+fn apply_all_test() {
+    let transforms = vec![
+        Transform::NormalizeWidth,
+        Transform::CollapseWhitespace,
+        Transform::Truncate(7),
+        Transform::Suffix("…".into()),
+    ];
+
+    assert_eq!(
+        "2020/8/…",
+        apply_all(&transforms, " ２０２０/８/１０\n  ２１:０５ ".into())
+    );
+    assert_eq!("as is", apply_all(&[], "as is".into()));
+}
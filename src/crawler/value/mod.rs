@@ -0,0 +1,149 @@
+#[cfg(test)]
+mod test;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json;
+use std::fmt;
+
+// extracted value of an artifact, typed by the ValueType of its selector
+#[derive(Clone, PartialEq, Debug, Serialize)]
+#[serde(untagged)]
+pub enum Value {
+    Text(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    DateTime(DateTime<FixedOffset>),
+    Url(String),
+    // several values of a column, see MultiValue::Array
+    List(Vec<Value>),
+}
+
+impl Value {
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            Value::Text(s) | Value::Url(s) => serde_json::Value::String(s.clone()),
+            Value::Integer(i) => serde_json::Value::from(*i),
+            Value::Float(f) => serde_json::Value::from(*f),
+            Value::Boolean(b) => serde_json::Value::Bool(*b),
+            Value::DateTime(dt) => serde_json::Value::String(dt.to_rfc3339()),
+            Value::List(values) => values.iter().map(Value::to_json).collect(),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Text(s) | Value::Url(s) => write!(f, "{}", s),
+            Value::Integer(i) => write!(f, "{}", i),
+            Value::Float(x) => write!(f, "{}", x),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::DateTime(dt) => write!(f, "{}", dt.to_rfc3339()),
+            Value::List(values) => {
+                let texts = values.iter().map(Value::to_string).collect::<Vec<String>>();
+                write!(f, "{}", texts.join(", "))
+            }
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::Text(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::Text(s)
+    }
+}
+
+// type hint of a selector
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum ValueType {
+    #[default]
+    Text,
+    DateTime {
+        // strftime formats tried in order. RFC 3339 and RFC 2822 are tried if empty
+        #[serde(default)]
+        formats: Vec<String>,
+        // tz database name like "Asia/Tokyo", used when the text has no offset. UTC if None
+        #[serde(default)]
+        timezone: Option<String>,
+    },
+    Integer,
+    Float,
+    Url,
+    Boolean,
+}
+
+impl ValueType {
+    // base_url is the url of the page the text was extracted from
+    pub fn parse(&self, text: &str, base_url: &str) -> Result<Value> {
+        let trimmed = text.trim();
+        match self {
+            ValueType::Text => Ok(Value::Text(text.to_string())),
+            ValueType::DateTime { formats, timezone } => {
+                parse_datetime(trimmed, formats, timezone.as_deref()).map(Value::DateTime)
+            }
+            ValueType::Integer => Ok(Value::Integer(strip_separators(trimmed).parse()?)),
+            ValueType::Float => Ok(Value::Float(strip_separators(trimmed).parse()?)),
+            ValueType::Url => Ok(Value::Url(Url::parse(base_url)?.join(trimmed)?.to_string())),
+            ValueType::Boolean => match trimmed.to_lowercase().as_str() {
+                "true" | "yes" | "on" | "1" => Ok(Value::Boolean(true)),
+                "false" | "no" | "off" | "0" => Ok(Value::Boolean(false)),
+                _ => Err(anyhow!("'{}' is not a boolean", text)),
+            },
+        }
+    }
+}
+
+// "16,845" -> "16845"
+fn strip_separators(text: &str) -> String {
+    text.chars().filter(|c| *c != ',' && *c != '_').collect()
+}
+
+fn parse_datetime(
+    text: &str,
+    formats: &[String],
+    timezone: Option<&str>,
+) -> Result<DateTime<FixedOffset>> {
+    if formats.is_empty() {
+        return DateTime::parse_from_rfc3339(text)
+            .or_else(|_| DateTime::parse_from_rfc2822(text))
+            .map_err(|_| anyhow!("'{}' is not a RFC 3339 or RFC 2822 datetime", text));
+    }
+
+    for format in formats {
+        if let Ok(dt) = DateTime::parse_from_str(text, format) {
+            return Ok(dt);
+        }
+        let naive = NaiveDateTime::parse_from_str(text, format).or_else(|_| {
+            NaiveDate::parse_from_str(text, format).map(|d| d.and_time(NaiveTime::MIN))
+        });
+        if let Ok(naive) = naive {
+            return localize(naive, timezone);
+        }
+    }
+
+    Err(anyhow!("'{}' does not match any of {:?}", text, formats))
+}
+
+fn localize(naive: NaiveDateTime, timezone: Option<&str>) -> Result<DateTime<FixedOffset>> {
+    let timezone = match timezone {
+        Some(timezone) => timezone,
+        None => return Ok(Utc.from_utc_datetime(&naive).into()),
+    };
+    let tz: Tz = timezone.parse().map_err(|e| anyhow!("{}", e))?;
+    let dt = tz
+        .from_local_datetime(&naive)
+        .earliest()
+        .ok_or_else(|| anyhow!("{} does not exist in {}", naive, timezone))?;
+    Ok(dt.with_timezone(&dt.offset().fix()))
+}
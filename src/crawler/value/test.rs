@@ -0,0 +1,84 @@
+use crate::crawler::value::{Value, ValueType};
+use chrono::{FixedOffset, TimeZone};
+
+#[test]
+fn value_type_parse_test() {
+    let base_url = "https://formula1-data.com/article/index.html";
+    let test_data = vec![
+        (
+            ValueType::Text,
+            " text ",
+            Some(Value::Text(" text ".into())),
+        ),
+        (
+            ValueType::DateTime {
+                formats: vec!["%Y/%m/%d %H:%M".into()],
+                timezone: Some("Asia/Tokyo".into()),
+            },
+            "2020/8/10 21:05",
+            Some(Value::DateTime(
+                FixedOffset::east_opt(9 * 3600)
+                    .unwrap()
+                    .with_ymd_and_hms(2020, 8, 10, 21, 5, 0)
+                    .unwrap(),
+            )),
+        ),
+        (
+            ValueType::DateTime {
+                formats: vec!["%Y-%m-%d".into()],
+                timezone: None,
+            },
+            "2020-08-10",
+            Some(Value::DateTime(
+                FixedOffset::east_opt(0)
+                    .unwrap()
+                    .with_ymd_and_hms(2020, 8, 10, 0, 0, 0)
+                    .unwrap(),
+            )),
+        ),
+        (
+            ValueType::DateTime {
+                formats: vec![],
+                timezone: None,
+            },
+            "2020-08-10T21:05:00+09:00",
+            Some(Value::DateTime(
+                FixedOffset::east_opt(9 * 3600)
+                    .unwrap()
+                    .with_ymd_and_hms(2020, 8, 10, 21, 5, 0)
+                    .unwrap(),
+            )),
+        ),
+        (
+            ValueType::DateTime {
+                formats: vec!["%Y/%m/%d".into()],
+                timezone: None,
+            },
+            "yesterday",
+            None,
+        ),
+        (ValueType::Integer, " 16,845 ", Some(Value::Integer(16845))),
+        (ValueType::Integer, "1.5", None),
+        (ValueType::Float, "1,234.5", Some(Value::Float(1234.5))),
+        (
+            ValueType::Url,
+            "/article/70th-anniversary",
+            Some(Value::Url(
+                "https://formula1-data.com/article/70th-anniversary".into(),
+            )),
+        ),
+        (
+            ValueType::Url,
+            "https://example.com/",
+            Some(Value::Url("https://example.com/".into())),
+        ),
+        (ValueType::Boolean, "Yes", Some(Value::Boolean(true))),
+        (ValueType::Boolean, "0", Some(Value::Boolean(false))),
+        (ValueType::Boolean, "maybe", None),
+    ];
+
+    for (value_type, text, expected) in test_data {
+        let actual = value_type.parse(text, base_url).ok();
+        assert_eq!(expected, actual, "{:?} {}", value_type, text)
+    }
+}
@@ -0,0 +1,371 @@
+use crate::crawler::SelectorTree;
+use crate::dispatcher::datastore::diff_content;
+use crate::dispatcher::query::{highlight, order, search_terms, snippet};
+use crate::dispatcher::{ContentCursor, ContentFilter, ContentPage, DataStore, Schedule};
+use crate::entity::{Content, ContentRevision, JobRun, SearchHit, Source};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, SubsecRound, TimeZone, Utc};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+// データベースを使わずにメモリ上に保存するDataStore。テストやdry runで使う
+// 1つのMutexで全体を守るので、それぞれの操作はSQLiteのtransactionと同じように全部反映されるか何も反映されない
+#[derive(Default)]
+pub struct MemoryDataStore {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    sources: BTreeMap<i32, Source>,
+    contents: Vec<Content>,
+    revisions: Vec<ContentRevision>,
+    job_runs: Vec<JobRun>,
+    // (source_id, url) -> (created_at, last_accessed)
+    accessed_urls: BTreeMap<(i32, String), (DateTime<Utc>, DateTime<Utc>)>,
+    last_source_id: i32,
+    last_revision_id: i32,
+}
+
+impl MemoryDataStore {
+    pub fn new() -> Self {
+        MemoryDataStore::default()
+    }
+}
+
+#[async_trait]
+impl DataStore for MemoryDataStore {
+    async fn get_stale_sources(&self) -> Result<Vec<Source>> {
+        let state = self.state.lock().unwrap();
+        let now = Utc::now();
+        Ok(state
+            .sources
+            .values()
+            .filter(|source| source.enabled && source.next_run <= now)
+            .cloned()
+            .collect())
+    }
+
+    async fn commit_job_result(
+        &self,
+        source_id: i32,
+        contents: Vec<Content>,
+        accessed_urls: Vec<String>,
+    ) -> Result<usize> {
+        let mut state = self.state.lock().unwrap();
+
+        // 失敗しうる処理を先に済ませ、途中までの結果が残らないようにする
+        // cursorはマイクロ秒までしか持たないので、PostgreSQLと同じくマイクロ秒までにする
+        let now = Utc::now().trunc_subsecs(6);
+        let next_run = match state.sources.get(&source_id) {
+            Some(source) => source.schedule.next_after(now)?,
+            None => return Err(anyhow!("source {} not found", source_id)),
+        };
+
+        let mut inserted = 0;
+        for content in contents {
+            let stored = state.contents.iter().position(|stored| {
+                stored.source_id == content.source_id && stored.url == content.url
+            });
+            match stored {
+                None => {
+                    state.contents.push(Content {
+                        created_at: now,
+                        ..content
+                    });
+                    inserted += 1;
+                }
+                Some(index) => {
+                    let stored = &state.contents[index];
+                    // タイトルと本文が変わっていなければ履歴は残さない
+                    if stored.title != content.title || stored.body != content.body {
+                        let revision = ContentRevision {
+                            id: state.last_revision_id + 1,
+                            content_id: stored.id.clone(),
+                            source_id: stored.source_id,
+                            url: stored.url.clone(),
+                            title: stored.title.clone(),
+                            body: stored.body.clone(),
+                            diff: diff_content(
+                                &stored.title,
+                                &stored.body,
+                                &content.title,
+                                &content.body,
+                            ),
+                            created_at: now,
+                        };
+                        state.last_revision_id = revision.id;
+                        state.revisions.push(revision);
+                    }
+
+                    let stored = &mut state.contents[index];
+                    stored.title = content.title;
+                    stored.body = content.body;
+                    stored.extra = content.extra;
+                    stored.published_at = content.published_at;
+                }
+            }
+        }
+
+        for url in accessed_urls {
+            state
+                .accessed_urls
+                .entry((source_id, url))
+                .and_modify(|(_, last_accessed)| *last_accessed = now)
+                .or_insert((now, now));
+        }
+        if let Some(source) = state.sources.get_mut(&source_id) {
+            source.last_accessed = now;
+            source.next_run = next_run;
+            source.consecutive_failures = 0;
+        }
+
+        Ok(inserted)
+    }
+
+    async fn get_sources(&self) -> Result<Vec<Source>> {
+        let state = self.state.lock().unwrap();
+        Ok(state.sources.values().cloned().collect())
+    }
+
+    async fn get_source(&self, source_id: i32) -> Result<Option<Source>> {
+        let state = self.state.lock().unwrap();
+        Ok(state.sources.get(&source_id).cloned())
+    }
+
+    async fn add_source(&self, selector_tree: SelectorTree) -> Result<i32> {
+        let mut state = self.state.lock().unwrap();
+        state.last_source_id += 1;
+        let id = state.last_source_id;
+        state.sources.insert(
+            id,
+            Source {
+                id,
+                name: selector_tree._id.clone(),
+                url: selector_tree.start_url.clone(),
+                selectors: selector_tree,
+                last_accessed: Utc.timestamp_opt(0, 0).unwrap(),
+                created_at: Utc::now(),
+                schedule: Schedule::default(),
+                enabled: true,
+                next_run: Utc.timestamp_opt(0, 0).unwrap(),
+                consecutive_failures: 0,
+            },
+        );
+        Ok(id)
+    }
+
+    async fn update_source(&self, source_id: i32, selector_tree: SelectorTree) -> Result<bool> {
+        let mut state = self.state.lock().unwrap();
+        match state.sources.get_mut(&source_id) {
+            Some(source) => {
+                source.url = selector_tree.start_url.clone();
+                source.selectors = selector_tree;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn rename_source(&self, source_id: i32, name: String) -> Result<bool> {
+        let mut state = self.state.lock().unwrap();
+        match state.sources.get_mut(&source_id) {
+            Some(source) => {
+                source.name = name;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn set_schedule(
+        &self,
+        source_id: i32,
+        schedule: Schedule,
+        enabled: bool,
+    ) -> Result<bool> {
+        let mut state = self.state.lock().unwrap();
+        match state.sources.get_mut(&source_id) {
+            Some(source) => {
+                source.next_run = schedule.next_after(source.last_accessed)?;
+                source.schedule = schedule;
+                source.enabled = enabled;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn set_enabled(&self, source_id: i32, enabled: bool) -> Result<bool> {
+        let mut state = self.state.lock().unwrap();
+        match state.sources.get_mut(&source_id) {
+            Some(source) => {
+                source.enabled = enabled;
+                if enabled {
+                    source.consecutive_failures = 0;
+                    source.next_run = Utc::now();
+                }
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn record_failure(
+        &self,
+        source_id: i32,
+        next_run: DateTime<Utc>,
+        disable: bool,
+    ) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(source) = state.sources.get_mut(&source_id) {
+            source.consecutive_failures += 1;
+            source.next_run = next_run;
+            if disable {
+                source.enabled = false;
+            }
+        }
+        Ok(())
+    }
+
+    async fn remove_source(&self, source_id: i32) -> Result<bool> {
+        let mut state = self.state.lock().unwrap();
+        state
+            .revisions
+            .retain(|revision| revision.source_id != source_id);
+        state
+            .contents
+            .retain(|content| content.source_id != source_id);
+        state
+            .job_runs
+            .retain(|job_run| job_run.source_id != source_id);
+        state.accessed_urls.retain(|(id, _), _| *id != source_id);
+        Ok(state.sources.remove(&source_id).is_some())
+    }
+
+    async fn get_revisions_since(
+        &self,
+        source_id: Option<i32>,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<ContentRevision>> {
+        let state = self.state.lock().unwrap();
+        let mut revisions = state
+            .revisions
+            .iter()
+            .filter(|revision| revision.created_at >= since)
+            .filter(|revision| source_id.is_none_or(|id| id == revision.source_id))
+            .cloned()
+            .collect::<Vec<ContentRevision>>();
+        revisions.sort_by_key(|revision| revision.created_at);
+        Ok(revisions)
+    }
+
+    async fn query_contents(
+        &self,
+        filter: ContentFilter,
+        cursor: Option<ContentCursor>,
+        limit: i64,
+    ) -> Result<ContentPage> {
+        let state = self.state.lock().unwrap();
+        let mut contents = state
+            .contents
+            .iter()
+            .filter(|content| filter.matches(content))
+            .filter(|content| {
+                cursor
+                    .as_ref()
+                    .is_none_or(|cursor| cursor.precedes(content))
+            })
+            .collect::<Vec<&Content>>();
+        contents.sort_by(|a, b| order(a.created_at, &a.id, b.created_at, &b.id));
+        let items = contents
+            .into_iter()
+            .take((limit.max(0) as usize).saturating_add(1))
+            .cloned()
+            .collect();
+        Ok(ContentPage::new(items, limit))
+    }
+
+    async fn count_contents(&self, filter: ContentFilter) -> Result<i64> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .contents
+            .iter()
+            .filter(|content| filter.matches(content))
+            .count() as i64)
+    }
+
+    // 語が何回現れたかを関連度にする
+    async fn search(
+        &self,
+        query: String,
+        filter: ContentFilter,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<SearchHit>> {
+        let terms = search_terms(&query)?;
+        let state = self.state.lock().unwrap();
+        let mut hits = state
+            .contents
+            .iter()
+            .filter(|content| filter.matches(content))
+            .filter_map(|content| {
+                let text = format!("{}\n{}", content.title, content.body).to_ascii_lowercase();
+                let counts = terms
+                    .iter()
+                    .map(|term| text.matches(&term.to_ascii_lowercase()).count())
+                    .collect::<Vec<usize>>();
+                if counts.contains(&0) {
+                    return None;
+                }
+                Some(SearchHit {
+                    content: content.clone(),
+                    score: counts.iter().sum::<usize>() as f64,
+                    title: highlight(&content.title, &terms),
+                    snippet: snippet(&content.body, &terms),
+                })
+            })
+            .collect::<Vec<SearchHit>>();
+        hits.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.content.id.cmp(&b.content.id))
+        });
+        Ok(hits
+            .into_iter()
+            .skip(offset.max(0) as usize)
+            .take(limit.max(0) as usize)
+            .collect())
+    }
+
+    async fn get_accessed_urls(&self, source_id: i32, since: DateTime<Utc>) -> Result<Vec<String>> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .accessed_urls
+            .iter()
+            .filter(|((id, _), (_, last_accessed))| *id == source_id && *last_accessed >= since)
+            .map(|((_, url), _)| url.clone())
+            .collect())
+    }
+
+    async fn add_job_run(&self, job_run: JobRun) -> Result<()> {
+        self.state.lock().unwrap().job_runs.push(job_run);
+        Ok(())
+    }
+
+    async fn get_job_runs(&self, source_id: i32, limit: i64) -> Result<Vec<JobRun>> {
+        let state = self.state.lock().unwrap();
+        let mut job_runs = state
+            .job_runs
+            .iter()
+            .rev()
+            .filter(|job_run| job_run.source_id == source_id)
+            .cloned()
+            .collect::<Vec<JobRun>>();
+        // started_atが同じなら後から記録したものを先にする
+        job_runs.sort_by_key(|job_run| std::cmp::Reverse(job_run.started_at));
+        job_runs.truncate(limit.max(0) as usize);
+        Ok(job_runs)
+    }
+}
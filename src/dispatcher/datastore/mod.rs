@@ -1,20 +1,68 @@
+#[cfg(test)]
+mod test;
+
+mod memory;
 mod models;
+mod postgres;
 mod schema;
 
+pub use memory::MemoryDataStore;
+pub use postgres::PgDataStoreAdapter;
+
 use crate::crawler::SelectorTree;
-use crate::dispatcher::datastore::models::SourceInsertModel;
-use crate::dispatcher::DataStore;
-use crate::entity::{Content, Source};
-use anyhow::{Error, Result};
+use crate::dispatcher::datastore::models::{
+    AccessedUrlInsertModel, ContentRevisionInsertModel, JobRunInsertModel, SourceInsertModel,
+};
+use crate::dispatcher::query::{fts_query, search_terms, MARK_END, MARK_START, SNIPPET_CHARS};
+use crate::dispatcher::{ContentCursor, ContentFilter, ContentPage, DataStore, Schedule};
+use crate::entity::{Content, ContentRevision, JobRun, SearchHit, Source};
+use anyhow::{anyhow, Error, Result};
 use async_trait::async_trait;
-use chrono::{DateTime, Duration, NaiveDate, Utc};
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
 use diesel;
+use diesel::dsl::sql;
 use diesel::prelude::*;
 use diesel::r2d2::{ConnectionManager, Pool};
-use diesel::sqlite::SqliteConnection;
-use models::{ContentModel, SourceModel};
+use diesel::sql_types::{BigInt, Integer, Nullable, Text, Timestamp};
+use diesel::sqlite::{Sqlite, SqliteConnection};
+use models::{
+    ContentModel, ContentQueryModel, ContentRevisionModel, JobRunModel, SearchModel, SourceModel,
+};
+use schema::{accessed_urls, content_revisions, contents, contents_fts, job_runs};
+use similar::TextDiff;
 use tokio;
 
+// DATABASE_URLのschemeがpostgres://かpostgresql://ならPostgreSQL、schemeがなければSQLiteのファイルとして開く
+// それ以外のschemeは書き間違いでSQLiteのファイルが作られないようにエラーにする
+pub fn connect(database_url: &str) -> Result<Box<dyn DataStore + Send + Sync>> {
+    match url_scheme(database_url) {
+        Some("postgres") | Some("postgresql") => Ok(Box::new(PgDataStoreAdapter::new(Pool::new(
+            ConnectionManager::new(database_url),
+        )?))),
+        // URLにはパスワードが含まれうるので、schemeだけを表示する
+        Some(scheme) => Err(anyhow!(
+            "unsupported database url scheme '{}://': use postgres:// or a path to a SQLite file",
+            scheme
+        )),
+        None => Ok(Box::new(DataStoreAdapter::new(Pool::new(
+            ConnectionManager::new(database_url),
+        )?))),
+    }
+}
+
+// RFC 3986のscheme (ALPHA *( ALPHA / DIGIT / "+" / "-" / "." )) に続けて://があればschemeを返す
+fn url_scheme(database_url: &str) -> Option<&str> {
+    let (scheme, _) = database_url.split_once("://")?;
+    let mut chars = scheme.chars();
+    let is_scheme = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.');
+    if is_scheme {
+        Some(scheme)
+    } else {
+        None
+    }
+}
+
 pub struct DataStoreAdapter {
     pool: Pool<ConnectionManager<SqliteConnection>>,
 }
@@ -27,42 +75,31 @@ impl DataStoreAdapter {
 
 #[async_trait]
 impl DataStore for DataStoreAdapter {
-    async fn get_stale_sources(&self, offset: Duration) -> Result<Vec<Source>> {
+    async fn get_stale_sources(&self) -> Result<Vec<Source>> {
         use schema::sources::dsl::*;
         let pool = self.pool.clone();
         let results: Vec<SourceModel> = tokio::task::spawn_blocking(move || -> Result<_> {
             let con = pool.get()?;
             Ok(sources
-                .filter(last_accessed.lt(Utc::now().naive_utc() - offset))
+                .filter(enabled.eq(true))
+                .filter(next_run.le(Utc::now().naive_utc()))
                 .load::<SourceModel>(&con)?)
         })
         .await??;
 
-        let (sources_results, errors): (Vec<Result<Source>>, Vec<Result<Source>>) = results
-            .into_iter()
-            .map(|model| {
-                Ok(Source {
-                    id: model.id,
-                    name: model.name,
-                    url: model.url,
-                    selectors: SelectorTree::from_json(model.selectors)?,
-                    last_accessed: DateTime::<Utc>::from_utc(model.last_accessed, Utc),
-                    last_accessed_urls: model
-                        .last_accessed_urls
-                        .split(",")
-                        .map(str::to_string)
-                        .collect(),
-                    created_at: DateTime::<Utc>::from_utc(model.created_at, Utc),
-                })
-            })
-            .partition(Result::is_ok);
-        if errors.len() != 0 {
-            let errors: Vec<Error> = errors.into_iter().map(Result::unwrap_err).collect();
-            for error in errors {
-                println!("{:?}", error)
-            }
-        };
-        sources_results.into_iter().collect()
+        to_sources(results)
+    }
+
+    async fn get_sources(&self) -> Result<Vec<Source>> {
+        use schema::sources::dsl::*;
+        let pool = self.pool.clone();
+        let results: Vec<SourceModel> = tokio::task::spawn_blocking(move || -> Result<_> {
+            let con = pool.get()?;
+            Ok(sources.order(id.asc()).load::<SourceModel>(&con)?)
+        })
+        .await??;
+
+        to_sources(results)
     }
 
     async fn commit_job_result(
@@ -70,77 +107,668 @@ impl DataStore for DataStoreAdapter {
         source_id: i32,
         contents_entities: Vec<Content>,
         accessed_urls: Vec<String>,
-    ) -> Result<()> {
+    ) -> Result<usize> {
         let contents_models = contents_entities
             .into_iter()
-            .map(|entity| ContentModel {
-                id: entity.id,
-                url: entity.url,
-                source_id: entity.source_id,
-                title: entity.title,
-                body: entity.body,
+            .map(|entity| {
+                Ok(ContentModel {
+                    id: entity.id,
+                    url: entity.url,
+                    source_id: entity.source_id,
+                    title: entity.title,
+                    body: entity.body,
+                    extra: serde_json::to_string(&entity.extra)?,
+                    published_at: entity.published_at.map(|dt| dt.naive_utc()),
+                })
             })
-            .collect::<Vec<ContentModel>>();
+            .collect::<Result<Vec<ContentModel>>>()?;
 
         let pool = self.pool.clone();
         let insert_result = tokio::task::spawn_blocking(move || {
             let con = pool.get()?;
 
             con.transaction::<_, Error, _>(|| {
-                {
-                    use schema::contents::dsl::*;
-                    diesel::insert_into(contents)
-                        .values(&contents_models)
+                let mut inserted = 0;
+                for model in &contents_models {
+                    let stored = contents::table
+                        .filter(contents::source_id.eq(model.source_id))
+                        .filter(contents::url.eq(&model.url))
+                        .select((contents::id, contents::title, contents::body))
+                        .first::<(String, String, String)>(&*con)
+                        .optional()?;
+
+                    match stored {
+                        None => {
+                            diesel::insert_into(contents::table)
+                                .values(model)
+                                .execute(&*con)?;
+                            diesel::insert_into(contents_fts::table)
+                                .values((
+                                    contents_fts::content_id.eq(&model.id),
+                                    contents_fts::title.eq(&model.title),
+                                    contents_fts::body.eq(&model.body),
+                                ))
+                                .execute(&*con)?;
+                            inserted += 1;
+                        }
+                        Some((stored_id, stored_title, stored_body)) => {
+                            // タイトルと本文が変わっていなければ履歴は残さない
+                            if stored_title != model.title || stored_body != model.body {
+                                diesel::insert_into(content_revisions::table)
+                                    .values(&ContentRevisionInsertModel {
+                                        content_id: stored_id.clone(),
+                                        diff: diff_content(
+                                            &stored_title,
+                                            &stored_body,
+                                            &model.title,
+                                            &model.body,
+                                        ),
+                                        title: stored_title,
+                                        body: stored_body,
+                                    })
+                                    .execute(&*con)?;
+                                diesel::update(
+                                    contents_fts::table
+                                        .filter(contents_fts::content_id.eq(&stored_id)),
+                                )
+                                .set((
+                                    contents_fts::title.eq(&model.title),
+                                    contents_fts::body.eq(&model.body),
+                                ))
+                                .execute(&*con)?;
+                            }
+                            diesel::update(contents::table.find(stored_id))
+                                .set((
+                                    contents::title.eq(&model.title),
+                                    contents::body.eq(&model.body),
+                                    contents::extra.eq(&model.extra),
+                                    contents::published_at.eq(&model.published_at),
+                                ))
+                                .execute(&*con)?;
+                        }
+                    }
+                }
+                let now = Utc::now();
+                for accessed_url in &accessed_urls {
+                    let target = accessed_urls::table
+                        .filter(accessed_urls::source_id.eq(source_id))
+                        .filter(accessed_urls::url.eq(accessed_url));
+                    let size = diesel::update(target)
+                        .set(accessed_urls::last_accessed.eq(now.naive_utc()))
                         .execute(&*con)?;
+                    if size == 0 {
+                        diesel::insert_into(accessed_urls::table)
+                            .values(&AccessedUrlInsertModel {
+                                source_id,
+                                url: accessed_url.clone(),
+                                last_accessed: now.naive_utc(),
+                            })
+                            .execute(&*con)?;
+                    }
                 }
                 {
                     use schema::sources::dsl::*;
                     let target_source = sources.filter(id.eq(source_id));
 
-                    if accessed_urls.len() != 0 {
-                        Ok(diesel::update(target_source)
-                            .set((
-                                last_accessed_urls.eq(accessed_urls.join(",")),
-                                last_accessed.eq(Utc::now().naive_utc()),
-                            ))
-                            .execute(&*con)?)
-                    } else {
-                        Ok(diesel::update(target_source)
-                            .set(last_accessed.eq(Utc::now().naive_utc()))
-                            .execute(&*con)?)
-                    }
+                    let (interval, expression, tz) = target_source
+                        .select((crawl_interval, cron, timezone))
+                        .first::<(i32, Option<String>, String)>(&*con)?;
+                    let next = to_schedule(interval, expression, tz)
+                        .next_after(now)?
+                        .naive_utc();
+
+                    diesel::update(target_source)
+                        .set((
+                            last_accessed.eq(now.naive_utc()),
+                            next_run.eq(next),
+                            consecutive_failures.eq(0),
+                        ))
+                        .execute(&*con)?;
                 }
+                Ok(inserted)
             })
         })
         .await?;
 
-        insert_result.map(|_| ())
+        insert_result
     }
 
-    async fn add_source(&self, selector_tree: SelectorTree) -> Result<()> {
+    async fn get_source(&self, source_id: i32) -> Result<Option<Source>> {
+        let pool = self.pool.clone();
+        let result: Option<SourceModel> = tokio::task::spawn_blocking(move || -> Result<_> {
+            let con = pool.get()?;
+            Ok(schema::sources::table
+                .find(source_id)
+                .first::<SourceModel>(&con)
+                .optional()?)
+        })
+        .await??;
+
+        result.map(to_source).transpose()
+    }
+
+    async fn add_source(&self, selector_tree: SelectorTree) -> Result<i32> {
         use schema::sources::dsl::*;
 
         let pool = self.pool.clone();
 
-        let insert_result: Result<_> = tokio::task::spawn_blocking(move || {
+        tokio::task::spawn_blocking(move || {
             let con = pool.get()?;
 
             let selectors_json = serde_json::to_string(&selector_tree)?;
 
-            let size = diesel::insert_into(sources)
-                .values(&SourceInsertModel {
-                    name: selector_tree._id,
-                    url: selector_tree.start_url,
-                    selectors: selectors_json,
-                    last_accessed: NaiveDate::from_ymd(1970, 1, 1).and_hms(0, 0, 0),
-                    last_accessed_urls: "".to_string(),
+            con.transaction::<_, Error, _>(|| {
+                diesel::insert_into(sources)
+                    .values(&SourceInsertModel {
+                        name: selector_tree._id,
+                        url: selector_tree.start_url,
+                        selectors: selectors_json,
+                        last_accessed: NaiveDateTime::UNIX_EPOCH,
+                    })
+                    .execute(&*con)?;
+
+                // SQLiteはRETURNINGが使えないので同じconnectionで直前のidを取る
+                Ok(diesel::select(sql::<Integer>("last_insert_rowid()")).get_result(&*con)?)
+            })
+        })
+        .await?
+    }
+
+    async fn update_source(&self, source_id: i32, selector_tree: SelectorTree) -> Result<bool> {
+        use schema::sources::dsl::*;
+
+        let pool = self.pool.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let con = pool.get()?;
+
+            let selectors_json = serde_json::to_string(&selector_tree)?;
+
+            let size = diesel::update(sources.find(source_id))
+                .set((
+                    url.eq(selector_tree.start_url),
+                    selectors.eq(selectors_json),
+                ))
+                .execute(&*con)?;
+
+            Ok(size != 0)
+        })
+        .await?
+    }
+
+    async fn rename_source(&self, source_id: i32, new_name: String) -> Result<bool> {
+        use schema::sources::dsl::*;
+
+        let pool = self.pool.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let con = pool.get()?;
+
+            let size = diesel::update(sources.find(source_id))
+                .set(name.eq(new_name))
+                .execute(&*con)?;
+
+            Ok(size != 0)
+        })
+        .await?
+    }
+
+    async fn set_schedule(
+        &self,
+        source_id: i32,
+        schedule: Schedule,
+        is_enabled: bool,
+    ) -> Result<bool> {
+        use schema::sources::dsl::*;
+
+        let pool = self.pool.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let con = pool.get()?;
+
+            con.transaction::<_, Error, _>(|| {
+                let accessed = match sources
+                    .find(source_id)
+                    .select(last_accessed)
+                    .first::<NaiveDateTime>(&*con)
+                    .optional()?
+                {
+                    Some(accessed) => Utc.from_utc_datetime(&accessed),
+                    None => return Ok(false),
+                };
+                let next = schedule.next_after(accessed)?.naive_utc();
+
+                let target = sources.find(source_id);
+                match schedule {
+                    Schedule::Interval { seconds } => diesel::update(target)
+                        .set((
+                            crawl_interval.eq(seconds),
+                            cron.eq(None::<String>),
+                            enabled.eq(is_enabled),
+                            next_run.eq(next),
+                        ))
+                        .execute(&*con)?,
+                    Schedule::Cron {
+                        expression,
+                        timezone: tz,
+                    } => diesel::update(target)
+                        .set((
+                            cron.eq(expression),
+                            timezone.eq(tz),
+                            enabled.eq(is_enabled),
+                            next_run.eq(next),
+                        ))
+                        .execute(&*con)?,
+                };
+
+                Ok(true)
+            })
+        })
+        .await?
+    }
+
+    async fn set_enabled(&self, source_id: i32, is_enabled: bool) -> Result<bool> {
+        use schema::sources::dsl::*;
+
+        let pool = self.pool.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let con = pool.get()?;
+
+            let target = sources.find(source_id);
+            let size = if is_enabled {
+                diesel::update(target)
+                    .set((
+                        enabled.eq(true),
+                        consecutive_failures.eq(0),
+                        next_run.eq(Utc::now().naive_utc()),
+                    ))
+                    .execute(&*con)?
+            } else {
+                diesel::update(target)
+                    .set(enabled.eq(false))
+                    .execute(&*con)?
+            };
+
+            Ok(size != 0)
+        })
+        .await?
+    }
+
+    async fn record_failure(
+        &self,
+        source_id: i32,
+        retry_at: DateTime<Utc>,
+        disable: bool,
+    ) -> Result<()> {
+        use schema::sources::dsl::*;
+
+        let pool = self.pool.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let con = pool.get()?;
+
+            diesel::update(sources.find(source_id))
+                .set((
+                    consecutive_failures.eq(consecutive_failures + 1),
+                    next_run.eq(retry_at.naive_utc()),
+                    enabled.eq(enabled.and(!disable)),
+                ))
+                .execute(&*con)?;
+
+            Ok(())
+        })
+        .await?
+    }
+
+    async fn remove_source(&self, target_source_id: i32) -> Result<bool> {
+        let pool = self.pool.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let con = pool.get()?;
+
+            con.transaction::<_, Error, _>(|| {
+                let content_ids = contents::table
+                    .filter(contents::source_id.eq(target_source_id))
+                    .select(contents::id);
+                diesel::delete(
+                    content_revisions::table
+                        .filter(content_revisions::content_id.eq_any(content_ids)),
+                )
+                .execute(&*con)?;
+                diesel::delete(
+                    contents_fts::table.filter(contents_fts::content_id.eq_any(content_ids)),
+                )
+                .execute(&*con)?;
+                diesel::delete(contents::table.filter(contents::source_id.eq(target_source_id)))
+                    .execute(&*con)?;
+                diesel::delete(job_runs::table.filter(job_runs::source_id.eq(target_source_id)))
+                    .execute(&*con)?;
+                diesel::delete(
+                    accessed_urls::table.filter(accessed_urls::source_id.eq(target_source_id)),
+                )
+                .execute(&*con)?;
+                let size =
+                    diesel::delete(schema::sources::table.find(target_source_id)).execute(&*con)?;
+
+                Ok(size != 0)
+            })
+        })
+        .await?
+    }
+
+    async fn get_revisions_since(
+        &self,
+        target_source_id: Option<i32>,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<ContentRevision>> {
+        let pool = self.pool.clone();
+        let results: Vec<ContentRevisionModel> =
+            tokio::task::spawn_blocking(move || -> Result<_> {
+                let con = pool.get()?;
+                let mut query = content_revisions::table
+                    .inner_join(contents::table)
+                    .select((
+                        content_revisions::id,
+                        content_revisions::content_id,
+                        contents::source_id,
+                        contents::url,
+                        content_revisions::title,
+                        content_revisions::body,
+                        content_revisions::diff,
+                        content_revisions::created_at,
+                    ))
+                    .filter(content_revisions::created_at.ge(since.naive_utc()))
+                    .order(content_revisions::created_at.asc())
+                    .into_boxed();
+                if let Some(target_source_id) = target_source_id {
+                    query = query.filter(contents::source_id.eq(target_source_id));
+                }
+                Ok(query.load::<ContentRevisionModel>(&con)?)
+            })
+            .await??;
+
+        Ok(results
+            .into_iter()
+            .map(|model| ContentRevision {
+                id: model.id,
+                content_id: model.content_id,
+                source_id: model.source_id,
+                url: model.url,
+                title: model.title,
+                body: model.body,
+                diff: model.diff,
+                created_at: Utc.from_utc_datetime(&model.created_at),
+            })
+            .collect())
+    }
+
+    async fn query_contents(
+        &self,
+        filter: ContentFilter,
+        cursor: Option<ContentCursor>,
+        limit: i64,
+    ) -> Result<ContentPage> {
+        let pool = self.pool.clone();
+        let results: Vec<ContentQueryModel> = tokio::task::spawn_blocking(move || -> Result<_> {
+            let con = pool.get()?;
+            let mut query = filter_contents(&filter)
+                .order((contents::created_at.desc(), contents::id.asc()))
+                .limit(limit.max(0).saturating_add(1));
+            if let Some(cursor) = cursor {
+                let cursor_created_at = cursor.created_at.naive_utc();
+                query = query.filter(
+                    contents::created_at
+                        .lt(cursor_created_at)
+                        .or(contents::created_at
+                            .eq(cursor_created_at)
+                            .and(contents::id.gt(cursor.id))),
+                );
+            }
+            Ok(query.load::<ContentQueryModel>(&con)?)
+        })
+        .await??;
+
+        let items = results
+            .into_iter()
+            .map(to_content)
+            .collect::<Result<Vec<Content>>>()?;
+        Ok(ContentPage::new(items, limit))
+    }
+
+    async fn count_contents(&self, filter: ContentFilter) -> Result<i64> {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            let con = pool.get()?;
+            Ok(filter_contents(&filter).count().get_result::<i64>(&con)?)
+        })
+        .await?
+    }
+
+    async fn search(
+        &self,
+        query: String,
+        filter: ContentFilter,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<SearchHit>> {
+        let terms = search_terms(&query)?;
+        let pool = self.pool.clone();
+        let results: Vec<SearchModel> = tokio::task::spawn_blocking(move || -> Result<_> {
+            let con = pool.get()?;
+            // bm25()は小さいほどよく合うので、符号を反転してscoreにする
+            // filterの条件はNULLなら絞り込まない
+            let query = diesel::sql_query(format!(
+                "SELECT contents.id, contents.url, contents.source_id, contents.title, \
+                    contents.body, contents.created_at, contents.extra, contents.published_at, \
+                    -bm25(contents_fts) AS score, \
+                    highlight(contents_fts, 1, '{start}', '{end}') AS highlighted_title, \
+                    snippet(contents_fts, 2, '{start}', '{end}', '…', {snippet}) AS snippet \
+                FROM contents_fts INNER JOIN contents ON contents.id = contents_fts.content_id \
+                WHERE contents_fts MATCH ?1 \
+                    AND (?2 IS NULL OR contents.source_id = ?2) \
+                    AND (?3 IS NULL OR contents.created_at >= ?3) \
+                    AND (?4 IS NULL OR contents.created_at < ?4) \
+                    AND (?5 IS NULL OR contents.title LIKE ?5 ESCAPE '\\' \
+                        OR contents.body LIKE ?5 ESCAPE '\\') \
+                ORDER BY score DESC, contents.id ASC \
+                LIMIT ?6 OFFSET ?7",
+                start = MARK_START,
+                end = MARK_END,
+                snippet = SNIPPET_CHARS,
+            ));
+            Ok(query
+                .bind::<Text, _>(fts_query(&terms))
+                .bind::<Nullable<Integer>, _>(filter.source_id)
+                .bind::<Nullable<Timestamp>, _>(filter.since.map(|dt| dt.naive_utc()))
+                .bind::<Nullable<Timestamp>, _>(filter.until.map(|dt| dt.naive_utc()))
+                .bind::<Nullable<Text>, _>(filter.like_pattern())
+                .bind::<BigInt, _>(limit.max(0))
+                .bind::<BigInt, _>(offset.max(0))
+                .load::<SearchModel>(&con)?)
+        })
+        .await??;
+
+        results
+            .into_iter()
+            .map(|model| {
+                Ok(SearchHit {
+                    score: model.score,
+                    title: model.highlighted_title,
+                    snippet: model.snippet,
+                    content: Content {
+                        id: model.id,
+                        url: model.url,
+                        source_id: model.source_id,
+                        title: model.title,
+                        body: model.body,
+                        extra: serde_json::from_str(&model.extra)?,
+                        published_at: model.published_at.map(|dt| Utc.from_utc_datetime(&dt)),
+                        created_at: Utc.from_utc_datetime(&model.created_at),
+                    },
+                })
+            })
+            .collect()
+    }
+
+    async fn get_accessed_urls(
+        &self,
+        target_source_id: i32,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<String>> {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            let con = pool.get()?;
+            Ok(accessed_urls::table
+                .filter(accessed_urls::source_id.eq(target_source_id))
+                .filter(accessed_urls::last_accessed.ge(since.naive_utc()))
+                .select(accessed_urls::url)
+                .order(accessed_urls::url.asc())
+                .load::<String>(&con)?)
+        })
+        .await?
+    }
+
+    async fn add_job_run(&self, job_run: JobRun) -> Result<()> {
+        let pool = self.pool.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let con = pool.get()?;
+
+            diesel::insert_into(job_runs::table)
+                .values(&JobRunInsertModel {
+                    source_id: job_run.source_id,
+                    started_at: job_run.started_at.naive_utc(),
+                    finished_at: job_run.finished_at.naive_utc(),
+                    status: job_run.status.as_str().to_string(),
+                    pages_fetched: job_run.pages_fetched,
+                    contents_inserted: job_run.contents_inserted,
+                    error: job_run.error,
                 })
                 .execute(&*con)?;
 
-            Ok(size)
+            Ok(())
         })
-        .await?;
+        .await?
+    }
 
-        insert_result.map(|_| ())
+    async fn get_job_runs(&self, target_source_id: i32, limit: i64) -> Result<Vec<JobRun>> {
+        let pool = self.pool.clone();
+        let results: Vec<JobRunModel> = tokio::task::spawn_blocking(move || -> Result<_> {
+            let con = pool.get()?;
+            Ok(job_runs::table
+                .filter(job_runs::source_id.eq(target_source_id))
+                .select((
+                    job_runs::source_id,
+                    job_runs::started_at,
+                    job_runs::finished_at,
+                    job_runs::status,
+                    job_runs::pages_fetched,
+                    job_runs::contents_inserted,
+                    job_runs::error,
+                ))
+                .order((job_runs::started_at.desc(), job_runs::id.desc()))
+                .limit(limit)
+                .load::<JobRunModel>(&con)?)
+        })
+        .await??;
+
+        results
+            .into_iter()
+            .map(|model| {
+                Ok(JobRun {
+                    source_id: model.source_id,
+                    started_at: Utc.from_utc_datetime(&model.started_at),
+                    finished_at: Utc.from_utc_datetime(&model.finished_at),
+                    status: model.status.parse()?,
+                    pages_fetched: model.pages_fetched,
+                    contents_inserted: model.contents_inserted,
+                    error: model.error,
+                })
+            })
+            .collect()
+    }
+}
+
+// selectorsが読めないsourceはログに出して除外する
+fn to_sources(models: Vec<SourceModel>) -> Result<Vec<Source>> {
+    let (sources_results, errors): (Vec<Result<Source>>, Vec<Result<Source>>) =
+        models.into_iter().map(to_source).partition(Result::is_ok);
+    for error in errors.into_iter().map(Result::unwrap_err) {
+        log::error!("{:?}", error)
+    }
+    sources_results.into_iter().collect()
+}
+
+fn to_source(model: SourceModel) -> Result<Source> {
+    Ok(Source {
+        id: model.id,
+        name: model.name,
+        url: model.url,
+        selectors: SelectorTree::from_json(model.selectors)?,
+        last_accessed: Utc.from_utc_datetime(&model.last_accessed),
+        created_at: Utc.from_utc_datetime(&model.created_at),
+        schedule: to_schedule(model.crawl_interval, model.cron, model.timezone),
+        enabled: model.enabled,
+        next_run: Utc.from_utc_datetime(&model.next_run),
+        consecutive_failures: model.consecutive_failures,
+    })
+}
+
+fn to_content(model: ContentQueryModel) -> Result<Content> {
+    Ok(Content {
+        id: model.id,
+        url: model.url,
+        source_id: model.source_id,
+        title: model.title,
+        body: model.body,
+        extra: serde_json::from_str(&model.extra)?,
+        published_at: model.published_at.map(|dt| Utc.from_utc_datetime(&dt)),
+        created_at: Utc.from_utc_datetime(&model.created_at),
+    })
+}
+
+// filterの条件で絞り込んだcontentsのquery
+fn filter_contents(filter: &ContentFilter) -> contents::BoxedQuery<'static, Sqlite> {
+    let mut query = contents::table.into_boxed();
+    if let Some(target_source_id) = filter.source_id {
+        query = query.filter(contents::source_id.eq(target_source_id));
+    }
+    if let Some(since) = filter.since {
+        query = query.filter(contents::created_at.ge(since.naive_utc()));
+    }
+    if let Some(until) = filter.until {
+        query = query.filter(contents::created_at.lt(until.naive_utc()));
     }
+    if let Some(pattern) = filter.like_pattern() {
+        query = query.filter(
+            contents::title
+                .like(pattern.clone())
+                .escape('\\')
+                .or(contents::body.like(pattern).escape('\\')),
+        );
+    }
+    query
+}
+
+fn to_schedule(crawl_interval: i32, cron: Option<String>, timezone: String) -> Schedule {
+    match cron {
+        Some(expression) => Schedule::Cron {
+            expression,
+            timezone,
+        },
+        None => Schedule::Interval {
+            seconds: crawl_interval,
+        },
+    }
+}
+
+// 変更前後のtitle, bodyのunified diffを作る
+fn diff_content(old_title: &str, old_body: &str, new_title: &str, new_body: &str) -> String {
+    let old = format!("{}\n{}\n", old_title, old_body);
+    let new = format!("{}\n{}\n", new_title, new_body);
+    TextDiff::from_lines(&old, &new)
+        .unified_diff()
+        .header("before", "after")
+        .to_string()
 }
@@ -1,6 +1,10 @@
+use crate::dispatcher::datastore::schema::accessed_urls;
+use crate::dispatcher::datastore::schema::content_revisions;
 use crate::dispatcher::datastore::schema::contents;
+use crate::dispatcher::datastore::schema::job_runs;
 use crate::dispatcher::datastore::schema::sources;
 use chrono::NaiveDateTime;
+use diesel::sql_types::{Double, Integer, Nullable, Text, Timestamp};
 
 #[derive(Debug, Queryable)]
 pub struct SourceModel {
@@ -9,8 +13,13 @@ pub struct SourceModel {
     pub url: String,
     pub selectors: String,
     pub last_accessed: NaiveDateTime,
-    pub last_accessed_urls: String,
     pub created_at: NaiveDateTime,
+    pub crawl_interval: i32,
+    pub cron: Option<String>,
+    pub timezone: String,
+    pub enabled: bool,
+    pub next_run: NaiveDateTime,
+    pub consecutive_failures: i32,
 }
 
 #[derive(Debug, Insertable)]
@@ -20,7 +29,6 @@ pub struct SourceInsertModel {
     pub url: String,
     pub selectors: String,
     pub last_accessed: NaiveDateTime,
-    pub last_accessed_urls: String,
 }
 
 #[derive(Debug, Insertable)]
@@ -31,4 +39,96 @@ pub struct ContentModel {
     pub source_id: i32,
     pub title: String,
     pub body: String,
+    pub extra: String,
+    pub published_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Queryable)]
+pub struct ContentQueryModel {
+    pub id: String,
+    pub url: String,
+    pub source_id: i32,
+    pub title: String,
+    pub body: String,
+    pub created_at: NaiveDateTime,
+    pub extra: String,
+    pub published_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "content_revisions"]
+pub struct ContentRevisionInsertModel {
+    pub content_id: String,
+    pub title: String,
+    pub body: String,
+    pub diff: String,
+}
+
+#[derive(Debug, Queryable)]
+pub struct ContentRevisionModel {
+    pub id: i32,
+    pub content_id: String,
+    pub source_id: i32,
+    pub url: String,
+    pub title: String,
+    pub body: String,
+    pub diff: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "job_runs"]
+pub struct JobRunInsertModel {
+    pub source_id: i32,
+    pub started_at: NaiveDateTime,
+    pub finished_at: NaiveDateTime,
+    pub status: String,
+    pub pages_fetched: i32,
+    pub contents_inserted: i32,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Queryable)]
+pub struct JobRunModel {
+    pub source_id: i32,
+    pub started_at: NaiveDateTime,
+    pub finished_at: NaiveDateTime,
+    pub status: String,
+    pub pages_fetched: i32,
+    pub contents_inserted: i32,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "accessed_urls"]
+pub struct AccessedUrlInsertModel {
+    pub source_id: i32,
+    pub url: String,
+    pub last_accessed: NaiveDateTime,
+}
+
+#[derive(Debug, QueryableByName)]
+pub struct SearchModel {
+    #[sql_type = "Text"]
+    pub id: String,
+    #[sql_type = "Text"]
+    pub url: String,
+    #[sql_type = "Integer"]
+    pub source_id: i32,
+    #[sql_type = "Text"]
+    pub title: String,
+    #[sql_type = "Text"]
+    pub body: String,
+    #[sql_type = "Timestamp"]
+    pub created_at: NaiveDateTime,
+    #[sql_type = "Text"]
+    pub extra: String,
+    #[sql_type = "Nullable<Timestamp>"]
+    pub published_at: Option<NaiveDateTime>,
+    #[sql_type = "Double"]
+    pub score: f64,
+    #[sql_type = "Text"]
+    pub highlighted_title: String,
+    #[sql_type = "Text"]
+    pub snippet: String,
 }
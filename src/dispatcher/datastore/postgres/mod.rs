@@ -0,0 +1,694 @@
+#[cfg(test)]
+mod test;
+
+mod models;
+mod schema;
+
+use crate::crawler::SelectorTree;
+use crate::dispatcher::datastore::{diff_content, to_schedule};
+use crate::dispatcher::query::{search_terms, MARK_END, MARK_START, SNIPPET_CHARS};
+use crate::dispatcher::{ContentCursor, ContentFilter, ContentPage, DataStore, Schedule};
+use crate::entity::{Content, ContentRevision, JobRun, SearchHit, Source};
+use anyhow::{Error, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, TimeZone, Utc};
+use diesel;
+use diesel::pg::upsert::excluded;
+use diesel::pg::{Pg, PgConnection};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::sql_types::{BigInt, Integer, Nullable, Text, Timestamptz};
+use models::{
+    AccessedUrlInsertModel, ContentModel, ContentQueryModel, ContentRevisionInsertModel,
+    ContentRevisionModel, JobRunInsertModel, JobRunModel, SearchModel, SourceInsertModel,
+    SourceModel,
+};
+use schema::{accessed_urls, content_revisions, contents, job_runs};
+use std::collections::BTreeSet;
+use tokio;
+
+// 1回のINSERTで送るaccessed_urlsの行数。bind parameterの上限(65535)を超えないようにする
+const ACCESSED_URLS_CHUNK_SIZE: usize = 1000;
+
+pub struct PgDataStoreAdapter {
+    pool: Pool<ConnectionManager<PgConnection>>,
+}
+
+impl PgDataStoreAdapter {
+    pub fn new(pool: Pool<ConnectionManager<PgConnection>>) -> Self {
+        PgDataStoreAdapter { pool }
+    }
+}
+
+#[async_trait]
+impl DataStore for PgDataStoreAdapter {
+    async fn get_stale_sources(&self) -> Result<Vec<Source>> {
+        use schema::sources::dsl::*;
+        let pool = self.pool.clone();
+        let results: Vec<SourceModel> = tokio::task::spawn_blocking(move || -> Result<_> {
+            let con = pool.get()?;
+            Ok(sources
+                .filter(enabled.eq(true))
+                .filter(next_run.le(Utc::now()))
+                .load::<SourceModel>(&con)?)
+        })
+        .await??;
+
+        to_sources(results)
+    }
+
+    async fn get_sources(&self) -> Result<Vec<Source>> {
+        use schema::sources::dsl::*;
+        let pool = self.pool.clone();
+        let results: Vec<SourceModel> = tokio::task::spawn_blocking(move || -> Result<_> {
+            let con = pool.get()?;
+            Ok(sources.order(id.asc()).load::<SourceModel>(&con)?)
+        })
+        .await??;
+
+        to_sources(results)
+    }
+
+    async fn commit_job_result(
+        &self,
+        source_id: i32,
+        contents_entities: Vec<Content>,
+        accessed_urls: Vec<String>,
+    ) -> Result<usize> {
+        let contents_models = contents_entities
+            .into_iter()
+            .map(|entity| ContentModel {
+                id: entity.id,
+                url: entity.url,
+                source_id: entity.source_id,
+                title: entity.title,
+                body: entity.body,
+                extra: entity.extra.into(),
+                published_at: entity.published_at,
+            })
+            .collect::<Vec<ContentModel>>();
+
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            let con = pool.get()?;
+
+            con.transaction::<_, Error, _>(|| {
+                let mut inserted = 0;
+                for model in &contents_models {
+                    let stored = contents::table
+                        .filter(contents::source_id.eq(model.source_id))
+                        .filter(contents::url.eq(&model.url))
+                        .select((contents::id, contents::title, contents::body))
+                        .first::<(String, String, String)>(&*con)
+                        .optional()?;
+
+                    match stored {
+                        None => {
+                            diesel::insert_into(contents::table)
+                                .values(model)
+                                .execute(&*con)?;
+                            inserted += 1;
+                        }
+                        Some((stored_id, stored_title, stored_body)) => {
+                            // タイトルと本文が変わっていなければ履歴は残さない
+                            if stored_title != model.title || stored_body != model.body {
+                                diesel::insert_into(content_revisions::table)
+                                    .values(&ContentRevisionInsertModel {
+                                        content_id: stored_id.clone(),
+                                        diff: diff_content(
+                                            &stored_title,
+                                            &stored_body,
+                                            &model.title,
+                                            &model.body,
+                                        ),
+                                        title: stored_title,
+                                        body: stored_body,
+                                    })
+                                    .execute(&*con)?;
+                            }
+                            diesel::update(contents::table.find(stored_id))
+                                .set((
+                                    contents::title.eq(&model.title),
+                                    contents::body.eq(&model.body),
+                                    contents::extra.eq(&model.extra),
+                                    contents::published_at.eq(&model.published_at),
+                                ))
+                                .execute(&*con)?;
+                        }
+                    }
+                }
+                let now = Utc::now();
+                // 同じurlが2回あるとON CONFLICTで同じ行を2回更新することになり失敗する
+                let accessed_models = accessed_urls
+                    .iter()
+                    .collect::<BTreeSet<&String>>()
+                    .into_iter()
+                    .map(|accessed_url| AccessedUrlInsertModel {
+                        source_id,
+                        url: accessed_url.clone(),
+                        last_accessed: now,
+                    })
+                    .collect::<Vec<AccessedUrlInsertModel>>();
+                for chunk in accessed_models.chunks(ACCESSED_URLS_CHUNK_SIZE) {
+                    diesel::insert_into(accessed_urls::table)
+                        .values(chunk)
+                        .on_conflict((accessed_urls::source_id, accessed_urls::url))
+                        .do_update()
+                        .set(
+                            accessed_urls::last_accessed.eq(excluded(accessed_urls::last_accessed)),
+                        )
+                        .execute(&*con)?;
+                }
+                {
+                    use schema::sources::dsl::*;
+                    let target_source = sources.filter(id.eq(source_id));
+
+                    let (interval, expression, tz) = target_source
+                        .select((crawl_interval, cron, timezone))
+                        .first::<(i32, Option<String>, String)>(&*con)?;
+                    let next = to_schedule(interval, expression, tz).next_after(now)?;
+
+                    diesel::update(target_source)
+                        .set((
+                            last_accessed.eq(now),
+                            next_run.eq(next),
+                            consecutive_failures.eq(0),
+                        ))
+                        .execute(&*con)?;
+                }
+                Ok(inserted)
+            })
+        })
+        .await?
+    }
+
+    async fn get_source(&self, source_id: i32) -> Result<Option<Source>> {
+        let pool = self.pool.clone();
+        let result: Option<SourceModel> = tokio::task::spawn_blocking(move || -> Result<_> {
+            let con = pool.get()?;
+            Ok(schema::sources::table
+                .find(source_id)
+                .first::<SourceModel>(&con)
+                .optional()?)
+        })
+        .await??;
+
+        result.map(to_source).transpose()
+    }
+
+    async fn add_source(&self, selector_tree: SelectorTree) -> Result<i32> {
+        use schema::sources::dsl::*;
+
+        let pool = self.pool.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let con = pool.get()?;
+
+            let selectors_json = serde_json::to_value(&selector_tree)?;
+
+            Ok(diesel::insert_into(sources)
+                .values(&SourceInsertModel {
+                    name: selector_tree._id,
+                    url: selector_tree.start_url,
+                    selectors: selectors_json,
+                    last_accessed: Utc.timestamp_opt(0, 0).unwrap(),
+                })
+                .returning(id)
+                .get_result(&con)?)
+        })
+        .await?
+    }
+
+    async fn update_source(&self, source_id: i32, selector_tree: SelectorTree) -> Result<bool> {
+        use schema::sources::dsl::*;
+
+        let pool = self.pool.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let con = pool.get()?;
+
+            let selectors_json = serde_json::to_value(&selector_tree)?;
+
+            let size = diesel::update(sources.find(source_id))
+                .set((
+                    url.eq(selector_tree.start_url),
+                    selectors.eq(selectors_json),
+                ))
+                .execute(&con)?;
+
+            Ok(size != 0)
+        })
+        .await?
+    }
+
+    async fn rename_source(&self, source_id: i32, new_name: String) -> Result<bool> {
+        use schema::sources::dsl::*;
+
+        let pool = self.pool.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let con = pool.get()?;
+
+            let size = diesel::update(sources.find(source_id))
+                .set(name.eq(new_name))
+                .execute(&*con)?;
+
+            Ok(size != 0)
+        })
+        .await?
+    }
+
+    async fn set_schedule(
+        &self,
+        source_id: i32,
+        schedule: Schedule,
+        is_enabled: bool,
+    ) -> Result<bool> {
+        use schema::sources::dsl::*;
+
+        let pool = self.pool.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let con = pool.get()?;
+
+            con.transaction::<_, Error, _>(|| {
+                let accessed = match sources
+                    .find(source_id)
+                    .select(last_accessed)
+                    .first::<DateTime<Utc>>(&*con)
+                    .optional()?
+                {
+                    Some(accessed) => accessed,
+                    None => return Ok(false),
+                };
+                let next = schedule.next_after(accessed)?;
+
+                let target = sources.find(source_id);
+                match schedule {
+                    Schedule::Interval { seconds } => diesel::update(target)
+                        .set((
+                            crawl_interval.eq(seconds),
+                            cron.eq(None::<String>),
+                            enabled.eq(is_enabled),
+                            next_run.eq(next),
+                        ))
+                        .execute(&*con)?,
+                    Schedule::Cron {
+                        expression,
+                        timezone: tz,
+                    } => diesel::update(target)
+                        .set((
+                            cron.eq(expression),
+                            timezone.eq(tz),
+                            enabled.eq(is_enabled),
+                            next_run.eq(next),
+                        ))
+                        .execute(&*con)?,
+                };
+
+                Ok(true)
+            })
+        })
+        .await?
+    }
+
+    async fn set_enabled(&self, source_id: i32, is_enabled: bool) -> Result<bool> {
+        use schema::sources::dsl::*;
+
+        let pool = self.pool.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let con = pool.get()?;
+
+            let target = sources.find(source_id);
+            let size = if is_enabled {
+                diesel::update(target)
+                    .set((
+                        enabled.eq(true),
+                        consecutive_failures.eq(0),
+                        next_run.eq(Utc::now()),
+                    ))
+                    .execute(&con)?
+            } else {
+                diesel::update(target)
+                    .set(enabled.eq(false))
+                    .execute(&con)?
+            };
+
+            Ok(size != 0)
+        })
+        .await?
+    }
+
+    async fn record_failure(
+        &self,
+        source_id: i32,
+        retry_at: DateTime<Utc>,
+        disable: bool,
+    ) -> Result<()> {
+        use schema::sources::dsl::*;
+
+        let pool = self.pool.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let con = pool.get()?;
+
+            let target = sources.find(source_id);
+            diesel::update(target)
+                .set((
+                    consecutive_failures.eq(consecutive_failures + 1),
+                    next_run.eq(retry_at),
+                    enabled.eq(enabled.and(!disable)),
+                ))
+                .execute(&con)?;
+
+            Ok(())
+        })
+        .await?
+    }
+
+    async fn remove_source(&self, target_source_id: i32) -> Result<bool> {
+        let pool = self.pool.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let con = pool.get()?;
+
+            con.transaction::<_, Error, _>(|| {
+                let content_ids = contents::table
+                    .filter(contents::source_id.eq(target_source_id))
+                    .select(contents::id);
+                diesel::delete(
+                    content_revisions::table
+                        .filter(content_revisions::content_id.eq_any(content_ids)),
+                )
+                .execute(&*con)?;
+                diesel::delete(contents::table.filter(contents::source_id.eq(target_source_id)))
+                    .execute(&*con)?;
+                diesel::delete(job_runs::table.filter(job_runs::source_id.eq(target_source_id)))
+                    .execute(&*con)?;
+                diesel::delete(
+                    accessed_urls::table.filter(accessed_urls::source_id.eq(target_source_id)),
+                )
+                .execute(&*con)?;
+                let size =
+                    diesel::delete(schema::sources::table.find(target_source_id)).execute(&*con)?;
+
+                Ok(size != 0)
+            })
+        })
+        .await?
+    }
+
+    async fn get_revisions_since(
+        &self,
+        target_source_id: Option<i32>,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<ContentRevision>> {
+        let pool = self.pool.clone();
+        let results: Vec<ContentRevisionModel> =
+            tokio::task::spawn_blocking(move || -> Result<_> {
+                let con = pool.get()?;
+                let mut query = content_revisions::table
+                    .inner_join(contents::table)
+                    .select((
+                        content_revisions::id,
+                        content_revisions::content_id,
+                        contents::source_id,
+                        contents::url,
+                        content_revisions::title,
+                        content_revisions::body,
+                        content_revisions::diff,
+                        content_revisions::created_at,
+                    ))
+                    .filter(content_revisions::created_at.ge(since))
+                    .order(content_revisions::created_at.asc())
+                    .into_boxed();
+                if let Some(target_source_id) = target_source_id {
+                    query = query.filter(contents::source_id.eq(target_source_id));
+                }
+                Ok(query.load::<ContentRevisionModel>(&con)?)
+            })
+            .await??;
+
+        Ok(results
+            .into_iter()
+            .map(|model| ContentRevision {
+                id: model.id,
+                content_id: model.content_id,
+                source_id: model.source_id,
+                url: model.url,
+                title: model.title,
+                body: model.body,
+                diff: model.diff,
+                created_at: model.created_at,
+            })
+            .collect())
+    }
+
+    async fn query_contents(
+        &self,
+        filter: ContentFilter,
+        cursor: Option<ContentCursor>,
+        limit: i64,
+    ) -> Result<ContentPage> {
+        let pool = self.pool.clone();
+        let results: Vec<ContentQueryModel> = tokio::task::spawn_blocking(move || -> Result<_> {
+            let con = pool.get()?;
+            let mut query = filter_contents(&filter)
+                .order((contents::created_at.desc(), contents::id.asc()))
+                .limit(limit.max(0).saturating_add(1));
+            if let Some(cursor) = cursor {
+                query = query.filter(
+                    contents::created_at
+                        .lt(cursor.created_at)
+                        .or(contents::created_at
+                            .eq(cursor.created_at)
+                            .and(contents::id.gt(cursor.id))),
+                );
+            }
+            Ok(query.load::<ContentQueryModel>(&con)?)
+        })
+        .await??;
+
+        let items = results
+            .into_iter()
+            .map(to_content)
+            .collect::<Result<Vec<Content>>>()?;
+        Ok(ContentPage::new(items, limit))
+    }
+
+    async fn count_contents(&self, filter: ContentFilter) -> Result<i64> {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            let con = pool.get()?;
+            Ok(filter_contents(&filter).count().get_result::<i64>(&con)?)
+        })
+        .await?
+    }
+
+    // contents_searchのindexと同じtsvectorで探す。'simple'なので語は単語単位で合う
+    async fn search(
+        &self,
+        query: String,
+        filter: ContentFilter,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<SearchHit>> {
+        let terms = search_terms(&query)?;
+        let pool = self.pool.clone();
+        let results: Vec<SearchModel> = tokio::task::spawn_blocking(move || -> Result<_> {
+            let con = pool.get()?;
+            let query = diesel::sql_query(format!(
+                "SELECT id, url, source_id, title, body, created_at, extra, published_at, \
+                    ts_rank(to_tsvector('simple', title || ' ' || body), query)::float8 AS score, \
+                    ts_headline('simple', title, query, \
+                        'StartSel={start}, StopSel={end}, HighlightAll=true') AS highlighted_title, \
+                    ts_headline('simple', body, query, \
+                        'StartSel={start}, StopSel={end}, MaxWords={words}, MinWords={min_words}, \
+                        ShortWord=0, FragmentDelimiter=…') AS snippet \
+                FROM contents, plainto_tsquery('simple', $1) AS query \
+                WHERE to_tsvector('simple', title || ' ' || body) @@ query \
+                    AND ($2 IS NULL OR source_id = $2) \
+                    AND ($3 IS NULL OR created_at >= $3) \
+                    AND ($4 IS NULL OR created_at < $4) \
+                    AND ($5 IS NULL OR title ILIKE $5 OR body ILIKE $5) \
+                ORDER BY score DESC, id ASC \
+                LIMIT $6 OFFSET $7",
+                start = MARK_START,
+                end = MARK_END,
+                words = SNIPPET_CHARS / 4,
+                min_words = SNIPPET_CHARS / 8,
+            ));
+            Ok(query
+                .bind::<Text, _>(terms.join(" "))
+                .bind::<Nullable<Integer>, _>(filter.source_id)
+                .bind::<Nullable<Timestamptz>, _>(filter.since)
+                .bind::<Nullable<Timestamptz>, _>(filter.until)
+                .bind::<Nullable<Text>, _>(filter.like_pattern())
+                .bind::<BigInt, _>(limit.max(0))
+                .bind::<BigInt, _>(offset.max(0))
+                .load::<SearchModel>(&con)?)
+        })
+        .await??;
+
+        results
+            .into_iter()
+            .map(|model| {
+                Ok(SearchHit {
+                    score: model.score,
+                    title: model.highlighted_title,
+                    snippet: model.snippet,
+                    content: to_content(ContentQueryModel {
+                        id: model.id,
+                        url: model.url,
+                        source_id: model.source_id,
+                        title: model.title,
+                        body: model.body,
+                        created_at: model.created_at,
+                        extra: model.extra,
+                        published_at: model.published_at,
+                    })?,
+                })
+            })
+            .collect()
+    }
+
+    async fn get_accessed_urls(
+        &self,
+        target_source_id: i32,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<String>> {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            let con = pool.get()?;
+            Ok(accessed_urls::table
+                .filter(accessed_urls::source_id.eq(target_source_id))
+                .filter(accessed_urls::last_accessed.ge(since))
+                .select(accessed_urls::url)
+                .order(accessed_urls::url.asc())
+                .load::<String>(&con)?)
+        })
+        .await?
+    }
+
+    async fn add_job_run(&self, job_run: JobRun) -> Result<()> {
+        let pool = self.pool.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let con = pool.get()?;
+
+            diesel::insert_into(job_runs::table)
+                .values(&JobRunInsertModel {
+                    source_id: job_run.source_id,
+                    started_at: job_run.started_at,
+                    finished_at: job_run.finished_at,
+                    status: job_run.status.as_str().to_string(),
+                    pages_fetched: job_run.pages_fetched,
+                    contents_inserted: job_run.contents_inserted,
+                    error: job_run.error,
+                })
+                .execute(&con)?;
+
+            Ok(())
+        })
+        .await?
+    }
+
+    async fn get_job_runs(&self, target_source_id: i32, limit: i64) -> Result<Vec<JobRun>> {
+        let pool = self.pool.clone();
+        let results: Vec<JobRunModel> = tokio::task::spawn_blocking(move || -> Result<_> {
+            let con = pool.get()?;
+            Ok(job_runs::table
+                .filter(job_runs::source_id.eq(target_source_id))
+                .select((
+                    job_runs::source_id,
+                    job_runs::started_at,
+                    job_runs::finished_at,
+                    job_runs::status,
+                    job_runs::pages_fetched,
+                    job_runs::contents_inserted,
+                    job_runs::error,
+                ))
+                .order((job_runs::started_at.desc(), job_runs::id.desc()))
+                .limit(limit)
+                .load::<JobRunModel>(&con)?)
+        })
+        .await??;
+
+        results
+            .into_iter()
+            .map(|model| {
+                Ok(JobRun {
+                    source_id: model.source_id,
+                    started_at: model.started_at,
+                    finished_at: model.finished_at,
+                    status: model.status.parse()?,
+                    pages_fetched: model.pages_fetched,
+                    contents_inserted: model.contents_inserted,
+                    error: model.error,
+                })
+            })
+            .collect()
+    }
+}
+
+// selectorsが読めないsourceはログに出して除外する
+fn to_sources(models: Vec<SourceModel>) -> Result<Vec<Source>> {
+    let (sources_results, errors): (Vec<Result<Source>>, Vec<Result<Source>>) =
+        models.into_iter().map(to_source).partition(Result::is_ok);
+    for error in errors.into_iter().map(Result::unwrap_err) {
+        log::error!("{:?}", error)
+    }
+    sources_results.into_iter().collect()
+}
+
+fn to_source(model: SourceModel) -> Result<Source> {
+    Ok(Source {
+        id: model.id,
+        name: model.name,
+        url: model.url,
+        selectors: serde_json::from_value(model.selectors)?,
+        last_accessed: model.last_accessed,
+        created_at: model.created_at,
+        schedule: to_schedule(model.crawl_interval, model.cron, model.timezone),
+        enabled: model.enabled,
+        next_run: model.next_run,
+        consecutive_failures: model.consecutive_failures,
+    })
+}
+
+fn to_content(model: ContentQueryModel) -> Result<Content> {
+    Ok(Content {
+        id: model.id,
+        url: model.url,
+        source_id: model.source_id,
+        title: model.title,
+        body: model.body,
+        extra: serde_json::from_value(model.extra)?,
+        published_at: model.published_at,
+        created_at: model.created_at,
+    })
+}
+
+// filterの条件で絞り込んだcontentsのquery。keywordは大文字小文字を区別しないようにILIKEで探す
+fn filter_contents(filter: &ContentFilter) -> contents::BoxedQuery<'static, Pg> {
+    let mut query = contents::table.into_boxed();
+    if let Some(target_source_id) = filter.source_id {
+        query = query.filter(contents::source_id.eq(target_source_id));
+    }
+    if let Some(since) = filter.since {
+        query = query.filter(contents::created_at.ge(since));
+    }
+    if let Some(until) = filter.until {
+        query = query.filter(contents::created_at.lt(until));
+    }
+    if let Some(pattern) = filter.like_pattern() {
+        // PostgreSQLのLIKEは'\'がescape文字なので、escape()を付けなくてよい
+        query = query.filter(
+            contents::title
+                .ilike(pattern.clone())
+                .or(contents::body.ilike(pattern)),
+        );
+    }
+    query
+}
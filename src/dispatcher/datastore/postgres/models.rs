@@ -0,0 +1,135 @@
+use crate::dispatcher::datastore::postgres::schema::accessed_urls;
+use crate::dispatcher::datastore::postgres::schema::content_revisions;
+use crate::dispatcher::datastore::postgres::schema::contents;
+use crate::dispatcher::datastore::postgres::schema::job_runs;
+use crate::dispatcher::datastore::postgres::schema::sources;
+use chrono::{DateTime, Utc};
+use diesel::sql_types::{Double, Integer, Jsonb, Nullable, Text, Timestamptz};
+use serde_json::Value;
+
+#[derive(Debug, Queryable)]
+pub struct SourceModel {
+    pub id: i32,
+    pub name: String,
+    pub url: String,
+    pub selectors: Value,
+    pub last_accessed: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub crawl_interval: i32,
+    pub cron: Option<String>,
+    pub timezone: String,
+    pub enabled: bool,
+    pub next_run: DateTime<Utc>,
+    pub consecutive_failures: i32,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "sources"]
+pub struct SourceInsertModel {
+    pub name: String,
+    pub url: String,
+    pub selectors: Value,
+    pub last_accessed: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "contents"]
+pub struct ContentModel {
+    pub id: String,
+    pub url: String,
+    pub source_id: i32,
+    pub title: String,
+    pub body: String,
+    pub extra: Value,
+    pub published_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Queryable)]
+pub struct ContentQueryModel {
+    pub id: String,
+    pub url: String,
+    pub source_id: i32,
+    pub title: String,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+    pub extra: Value,
+    pub published_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "content_revisions"]
+pub struct ContentRevisionInsertModel {
+    pub content_id: String,
+    pub title: String,
+    pub body: String,
+    pub diff: String,
+}
+
+#[derive(Debug, Queryable)]
+pub struct ContentRevisionModel {
+    pub id: i32,
+    pub content_id: String,
+    pub source_id: i32,
+    pub url: String,
+    pub title: String,
+    pub body: String,
+    pub diff: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "job_runs"]
+pub struct JobRunInsertModel {
+    pub source_id: i32,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub status: String,
+    pub pages_fetched: i32,
+    pub contents_inserted: i32,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Queryable)]
+pub struct JobRunModel {
+    pub source_id: i32,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub status: String,
+    pub pages_fetched: i32,
+    pub contents_inserted: i32,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "accessed_urls"]
+pub struct AccessedUrlInsertModel {
+    pub source_id: i32,
+    pub url: String,
+    pub last_accessed: DateTime<Utc>,
+}
+
+#[derive(Debug, QueryableByName)]
+pub struct SearchModel {
+    #[sql_type = "Text"]
+    pub id: String,
+    #[sql_type = "Text"]
+    pub url: String,
+    #[sql_type = "Integer"]
+    pub source_id: i32,
+    #[sql_type = "Text"]
+    pub title: String,
+    #[sql_type = "Text"]
+    pub body: String,
+    #[sql_type = "Timestamptz"]
+    pub created_at: DateTime<Utc>,
+    #[sql_type = "Jsonb"]
+    pub extra: Value,
+    #[sql_type = "Nullable<Timestamptz>"]
+    pub published_at: Option<DateTime<Utc>>,
+    #[sql_type = "Double"]
+    pub score: f64,
+    #[sql_type = "Text"]
+    pub highlighted_title: String,
+    #[sql_type = "Text"]
+    pub snippet: String,
+}
@@ -0,0 +1,75 @@
+table! {
+    accessed_urls (source_id, url) {
+        source_id -> Integer,
+        url -> Text,
+        created_at -> Timestamptz,
+        last_accessed -> Timestamptz,
+    }
+}
+
+table! {
+    content_revisions (id) {
+        id -> Integer,
+        content_id -> Text,
+        title -> Text,
+        body -> Text,
+        diff -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    contents (id) {
+        id -> Text,
+        url -> Text,
+        source_id -> Integer,
+        title -> Text,
+        body -> Text,
+        created_at -> Timestamptz,
+        extra -> Jsonb,
+        published_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    job_runs (id) {
+        id -> Integer,
+        source_id -> Integer,
+        started_at -> Timestamptz,
+        finished_at -> Timestamptz,
+        status -> Text,
+        pages_fetched -> Integer,
+        contents_inserted -> Integer,
+        error -> Nullable<Text>,
+    }
+}
+
+table! {
+    sources (id) {
+        id -> Integer,
+        name -> Text,
+        url -> Text,
+        selectors -> Jsonb,
+        last_accessed -> Timestamptz,
+        created_at -> Timestamptz,
+        crawl_interval -> Integer,
+        cron -> Nullable<Text>,
+        timezone -> Text,
+        enabled -> Bool,
+        next_run -> Timestamptz,
+        consecutive_failures -> Integer,
+    }
+}
+
+joinable!(accessed_urls -> sources (source_id));
+joinable!(content_revisions -> contents (content_id));
+joinable!(contents -> sources (source_id));
+joinable!(job_runs -> sources (source_id));
+
+allow_tables_to_appear_in_same_query!(
+    accessed_urls,
+    content_revisions,
+    contents,
+    job_runs,
+    sources,
+);
@@ -0,0 +1,312 @@
+use crate::crawler::SelectorTree;
+use crate::dispatcher::datastore::postgres::PgDataStoreAdapter;
+use crate::dispatcher::{ContentFilter, DataStore, Schedule};
+use crate::entity::{Content, JobRun, JobStatus};
+use chrono::{Duration, TimeZone, Utc};
+use diesel::connection::SimpleConnection;
+use diesel::pg::PgConnection;
+use diesel::r2d2::{ConnectionManager, Pool};
+use serde_json::{json, Map};
+use std::env;
+
+// runs only when TEST_POSTGRES_URL is set, because it drops and recreates every table
+fn data_store() -> Option<PgDataStoreAdapter> {
+    let database_url = match env::var("TEST_POSTGRES_URL") {
+        Ok(database_url) => database_url,
+        Err(_) => {
+            eprintln!("TEST_POSTGRES_URL is not set. skipped");
+            return None;
+        }
+    };
+    let pool = Pool::new(ConnectionManager::<PgConnection>::new(database_url)).unwrap();
+    let con = pool.get().unwrap();
+    // accessed_urls references sources, so it must be dropped before the initial down.sql
+    con.batch_execute("DROP TABLE IF EXISTS accessed_urls;")
+        .unwrap();
+    con.batch_execute(include_str!(
+        "../../../../migrations_postgres/2026-10-19-000000_initial/down.sql"
+    ))
+    .unwrap();
+    con.batch_execute(include_str!(
+        "../../../../migrations_postgres/2026-10-19-000000_initial/up.sql"
+    ))
+    .unwrap();
+    con.batch_execute(include_str!(
+        "../../../../migrations_postgres/2026-10-19-000001_accessed_urls/up.sql"
+    ))
+    .unwrap();
+    con.batch_execute(include_str!(
+        "../../../../migrations_postgres/2026-10-19-000002_contents_search/up.sql"
+    ))
+    .unwrap();
+    Some(PgDataStoreAdapter::new(pool))
+}
+
+fn content(source_id: i32, url: &str, title: &str) -> Content {
+    let mut extra = Map::new();
+    extra.insert("laps".to_string(), json!(53));
+    Content {
+        id: url.to_string(),
+        url: url.to_string(),
+        source_id,
+        title: title.to_string(),
+        body: "body".to_string(),
+        extra,
+        published_at: Some(Utc.with_ymd_and_hms(2020, 8, 1, 0, 0, 0).unwrap()),
+        created_at: Utc::now(),
+    }
+}
+
+#[tokio::test]
+async fn pg_data_store_test() {
+    let data_store = match data_store() {
+        Some(data_store) => data_store,
+        None => return,
+    };
+
+    let selector_tree = SelectorTree::new(
+        r#"{"_id": "formula1-data", "startUrl": ["https://formula1-data.com/article"], "selectors": []}"#
+            .to_string(),
+    )
+    .unwrap();
+    let id = data_store.add_source(selector_tree.clone()).await.unwrap();
+
+    let source = data_store.get_source(id).await.unwrap().unwrap();
+    assert_eq!(source.name, "formula1-data");
+    assert_eq!(source.selectors, selector_tree);
+    assert!(data_store
+        .rename_source(id, "F1 data".to_string())
+        .await
+        .unwrap());
+    let source = data_store.get_source(id).await.unwrap().unwrap();
+    assert_eq!(source.name, "F1 data");
+    assert_eq!(source.selectors, selector_tree);
+    // updating the sitemap keeps the name
+    assert!(data_store
+        .update_source(id, selector_tree.clone())
+        .await
+        .unwrap());
+    let source = data_store.get_source(id).await.unwrap().unwrap();
+    assert_eq!(source.name, "F1 data");
+    assert_eq!(source.schedule, Schedule::default());
+    assert_eq!(data_store.get_stale_sources().await.unwrap().len(), 1);
+
+    // urls keep their commas
+    let accessed_urls = vec![
+        "https://formula1-data.com/article?a=1,2".to_string(),
+        "https://formula1-data.com/article/1".to_string(),
+    ];
+    let inserted = data_store
+        .commit_job_result(
+            id,
+            vec![
+                content(id, "https://formula1-data.com/article/1", "title 1"),
+                content(id, "https://formula1-data.com/article/2", "title 2"),
+            ],
+            accessed_urls,
+        )
+        .await
+        .unwrap();
+    assert_eq!(inserted, 2);
+    let source = data_store.get_source(id).await.unwrap().unwrap();
+    assert!(source.next_run > Utc::now());
+    assert_eq!(data_store.get_stale_sources().await.unwrap().len(), 0);
+
+    let inserted = data_store
+        .commit_job_result(
+            id,
+            vec![content(
+                id,
+                "https://formula1-data.com/article/1",
+                "title 1 (updated)",
+            )],
+            vec![
+                "https://formula1-data.com/article/1".to_string(),
+                "https://formula1-data.com/article/1".to_string(),
+                "https://formula1-data.com/article/2".to_string(),
+            ],
+        )
+        .await
+        .unwrap();
+    assert_eq!(inserted, 0);
+    // a url accessed twice is stored once
+    let mut stored_urls = data_store
+        .get_accessed_urls(id, Utc::now() - Duration::minutes(1))
+        .await
+        .unwrap();
+    stored_urls.sort();
+    assert_eq!(
+        stored_urls,
+        vec![
+            "https://formula1-data.com/article/1".to_string(),
+            "https://formula1-data.com/article/2".to_string(),
+            "https://formula1-data.com/article?a=1,2".to_string(),
+        ]
+    );
+    assert!(data_store
+        .get_accessed_urls(id, Utc::now() + Duration::minutes(1))
+        .await
+        .unwrap()
+        .is_empty());
+    let revisions = data_store
+        .get_revisions_since(Some(id), Utc::now() - Duration::minutes(1))
+        .await
+        .unwrap();
+    assert_eq!(revisions.len(), 1);
+    assert_eq!(revisions[0].title, "title 1");
+
+    // the same title and body leave no revision, but the other columns are updated
+    let mut unchanged = content(id, "https://formula1-data.com/article/2", "title 2");
+    unchanged.extra.insert("laps".to_string(), json!(57));
+    unchanged.published_at = None;
+    data_store
+        .commit_job_result(id, vec![unchanged], vec![])
+        .await
+        .unwrap();
+    assert_eq!(
+        data_store
+            .get_revisions_since(Some(id), Utc::now() - Duration::minutes(1))
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+    let keyword = ContentFilter {
+        keyword: Some("title 2".to_string()),
+        ..ContentFilter::source(id)
+    };
+    let page = data_store.query_contents(keyword, None, 10).await.unwrap();
+    assert_eq!(page.items.len(), 1);
+    assert_eq!(page.items[0].extra["laps"], json!(57));
+    assert_eq!(page.items[0].published_at, None);
+    // one more content than the limit is queried, which must not overflow
+    let page = data_store
+        .query_contents(ContentFilter::source(id), None, i64::MAX)
+        .await
+        .unwrap();
+    assert_eq!(page.items.len(), 2);
+
+    let page = data_store
+        .query_contents(ContentFilter::source(id), None, 1)
+        .await
+        .unwrap();
+    assert_eq!(page.items.len(), 1);
+    assert!(page.next_cursor.is_some());
+    let contents = page.items;
+    let page = data_store
+        .query_contents(ContentFilter::source(id), page.next_cursor, 1)
+        .await
+        .unwrap();
+    assert_eq!(page.items.len(), 1);
+    assert_ne!(page.items[0].id, contents[0].id);
+    assert_eq!(page.next_cursor, None);
+    assert_eq!(
+        data_store
+            .count_contents(ContentFilter::source(id))
+            .await
+            .unwrap(),
+        2
+    );
+    let keyword = ContentFilter {
+        keyword: Some("TITLE 1 (".to_string()),
+        ..ContentFilter::source(id)
+    };
+    assert_eq!(data_store.count_contents(keyword).await.unwrap(), 1);
+    assert_eq!(contents[0].extra["laps"], json!(53));
+
+    let schedule = Schedule::Cron {
+        expression: "0 0 9 * * *".to_string(),
+        timezone: "Asia/Tokyo".to_string(),
+    };
+    assert!(data_store
+        .set_schedule(id, schedule.clone(), true)
+        .await
+        .unwrap());
+    data_store
+        .record_failure(id, Utc::now() + Duration::minutes(1), true)
+        .await
+        .unwrap();
+    let source = data_store.get_source(id).await.unwrap().unwrap();
+    assert_eq!(source.schedule, schedule);
+    assert!(!source.enabled);
+    assert_eq!(source.consecutive_failures, 1);
+    assert!(data_store.set_enabled(id, true).await.unwrap());
+    let source = data_store.get_source(id).await.unwrap().unwrap();
+    assert!(source.enabled);
+    assert_eq!(source.consecutive_failures, 0);
+
+    data_store
+        .add_job_run(JobRun {
+            source_id: id,
+            started_at: Utc.with_ymd_and_hms(2020, 8, 1, 0, 0, 0).unwrap(),
+            finished_at: Utc.with_ymd_and_hms(2020, 8, 1, 0, 1, 0).unwrap(),
+            status: JobStatus::Failed,
+            pages_fetched: 0,
+            contents_inserted: 0,
+            error: Some("404".to_string()),
+        })
+        .await
+        .unwrap();
+    let job_runs = data_store.get_job_runs(id, 10).await.unwrap();
+    assert_eq!(job_runs.len(), 1);
+    assert_eq!(job_runs[0].status, JobStatus::Failed);
+
+    assert!(data_store.remove_source(id).await.unwrap());
+    assert!(data_store.get_source(id).await.unwrap().is_none());
+    assert!(!data_store.remove_source(id).await.unwrap());
+
+    search_test(&data_store).await;
+}
+
+// every test recreates the tables, so this runs inside pg_data_store_test instead of in parallel
+async fn search_test(data_store: &PgDataStoreAdapter) {
+    let selector_tree = SelectorTree::new(
+        r#"{"_id": "formula1-data", "startUrl": ["https://formula1-data.com/article"], "selectors": []}"#
+            .to_string(),
+    )
+    .unwrap();
+    let id = data_store.add_source(selector_tree).await.unwrap();
+    let article = |i: i32, title: &str, body: &str| Content {
+        body: body.to_string(),
+        ..content(
+            id,
+            &format!("https://formula1-data.com/article/{}", i),
+            title,
+        )
+    };
+    data_store
+        .commit_job_result(
+            id,
+            vec![
+                article(1, "Hamilton wins", "Hamilton won the British Grand Prix."),
+                article(2, "Verstappen on pole", "Hamilton was third on the grid."),
+                article(3, "Pit stop", "A slow pit stop for Verstappen."),
+            ],
+            vec![],
+        )
+        .await
+        .unwrap();
+
+    let search =
+        |query: &str, filter: ContentFilter| data_store.search(query.to_string(), filter, 10, 0);
+    let hits = search("hamilton", ContentFilter::default()).await.unwrap();
+    assert_eq!(hits.len(), 2);
+    assert_eq!(hits[0].content.url, "https://formula1-data.com/article/1");
+    assert!(hits[0].score > hits[1].score);
+    assert_eq!(hits[0].title, "<mark>Hamilton</mark> wins");
+    assert!(hits[0].snippet.contains("<mark>Hamilton</mark> won"));
+    assert_eq!(hits[0].content.extra["laps"], 53);
+
+    let hits = search("verstappen pit", ContentFilter::default())
+        .await
+        .unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].content.url, "https://formula1-data.com/article/3");
+    assert!(search("hamilton", ContentFilter::source(id + 1))
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(search("pit go", ContentFilter::default()).await.is_err());
+
+    assert!(data_store.remove_source(id).await.unwrap());
+}
@@ -1,3 +1,23 @@
+table! {
+    accessed_urls (source_id, url) {
+        source_id -> Integer,
+        url -> Text,
+        created_at -> Timestamp,
+        last_accessed -> Timestamp,
+    }
+}
+
+table! {
+    content_revisions (id) {
+        id -> Integer,
+        content_id -> Text,
+        title -> Text,
+        body -> Text,
+        diff -> Text,
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     contents (id) {
         id -> Text,
@@ -6,6 +26,30 @@ table! {
         title -> Text,
         body -> Text,
         created_at -> Timestamp,
+        extra -> Text,
+        published_at -> Nullable<Timestamp>,
+    }
+}
+
+// FTS5のvirtual table。contentsと同じcontent_idの行をcommit_job_resultで更新する
+table! {
+    contents_fts (content_id) {
+        content_id -> Text,
+        title -> Text,
+        body -> Text,
+    }
+}
+
+table! {
+    job_runs (id) {
+        id -> Integer,
+        source_id -> Integer,
+        started_at -> Timestamp,
+        finished_at -> Timestamp,
+        status -> Text,
+        pages_fetched -> Integer,
+        contents_inserted -> Integer,
+        error -> Nullable<Text>,
     }
 }
 
@@ -16,11 +60,26 @@ table! {
         url -> Text,
         selectors -> Text,
         last_accessed -> Timestamp,
-        last_accessed_urls -> Text,
         created_at -> Timestamp,
+        crawl_interval -> Integer,
+        cron -> Nullable<Text>,
+        timezone -> Text,
+        enabled -> Bool,
+        next_run -> Timestamp,
+        consecutive_failures -> Integer,
     }
 }
 
+joinable!(accessed_urls -> sources (source_id));
+joinable!(content_revisions -> contents (content_id));
 joinable!(contents -> sources (source_id));
+joinable!(job_runs -> sources (source_id));
 
-allow_tables_to_appear_in_same_query!(contents, sources,);
+allow_tables_to_appear_in_same_query!(
+    accessed_urls,
+    content_revisions,
+    contents,
+    contents_fts,
+    job_runs,
+    sources,
+);
@@ -1,30 +1,108 @@
-use crate::crawler::{format, Crawler, WebFetcher};
+use crate::crawler::{
+    format_with_policy, Artifact, Column, ColumnPolicy, Crawler, FetchClient, FieldMapping, Record,
+    Value,
+};
 use crate::entity::{Content, Source};
-use anyhow::Result;
-use chrono::Utc;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use log;
+use serde_json::Map;
 use sha1::{Digest, Sha1};
 
-pub async fn kick(source: Source) -> Result<(i32, Vec<Content>, Vec<String>)> {
-    let crawler = Crawler::new(WebFetcher::new(), source.last_accessed_urls.clone());
-    let (artifacts, accessed_urls) = crawler.crawl(&source.selectors).await?;
+// sourceをcrawlして、取得したページ数と、保存するcontentsとaccessed_urlsを返す
+// 途中で失敗しても、それまでに取得したページ数は返す
+pub async fn kick<F: 'static + FetchClient + Send>(
+    fetcher: F,
+    source: Source,
+    skip_urls: Vec<String>,
+) -> (usize, Result<(Vec<Content>, Vec<String>)>) {
+    let crawler = Crawler::new(fetcher, skip_urls);
+    match crawler.crawl(&source.selectors).await {
+        Ok((artifacts, accessed_urls, pages)) => (
+            pages,
+            build_contents(&source, artifacts).map(|contents| (contents, accessed_urls)),
+        ),
+        Err(err) => (err.pages, Err(err.into())),
+    }
+}
+
+fn build_contents(source: &Source, artifacts: Vec<Artifact>) -> Result<Vec<Content>> {
+    let fields = if source.selectors.fields.is_empty() {
+        default_fields()
+    } else {
+        source.selectors.fields.clone()
+    };
+    let columns = fields
+        .iter()
+        .map(FieldMapping::to_column)
+        .collect::<Vec<Column>>();
+
+    let formatted = format_with_policy(artifacts, &columns)?;
+    for dropped in &formatted.dropped {
+        log::warn!("source {}: dropped {}", source.id, dropped);
+    }
 
-    let contents = format(artifacts, vec!["title", "body", "link", "source_url"])?
+    formatted
+        .rows
         .into_iter()
-        .map(|row| {
-            let mut hasher = Sha1::new();
-            hasher.update(format!("{}{}", row[2], Utc::now().to_rfc3339()));
-
-            Ok(Content {
-                id: format!("{:x}", hasher.finalize()),
-                url: (*row[2]).clone(),
-                source_id: source.id,
-                title: (*row[0]).clone(),
-                // TODO: fix overhead
-                body: (*row[1]).clone(),
-                created_at: Utc::now(),
-            })
-        })
-        .collect::<Result<Vec<Content>>>()?;
-
-    Ok((source.id, contents, accessed_urls))
+        .map(|record| build_content(source.id, &fields, &record))
+        .collect()
+}
+
+// fieldsの指定がないsourceはtitle, body, linkをそのまま使う
+fn default_fields() -> Vec<FieldMapping> {
+    vec![
+        FieldMapping::new("title", "title", ColumnPolicy::Required),
+        FieldMapping::new("body", "body", ColumnPolicy::Required),
+        FieldMapping::new("link", "url", ColumnPolicy::Required),
+    ]
+}
+
+fn build_content(source_id: i32, fields: &[FieldMapping], record: &Record) -> Result<Content> {
+    let mut url = None;
+    let mut title = String::new();
+    let mut body = String::new();
+    let mut published_at = None;
+    let mut extra = Map::new();
+
+    for field in fields {
+        let value = match record.get(&field.column) {
+            Some(value) => value,
+            None => continue,
+        };
+        match field.field.as_str() {
+            "url" => url = Some(value.to_string()),
+            "title" => title = value.to_string(),
+            "body" => body = value.to_string(),
+            "published_at" => published_at = Some(to_datetime(value)?),
+            other => {
+                extra.insert(other.to_string(), value.to_json());
+            }
+        }
+    }
+
+    let url = url.ok_or_else(|| anyhow!("field 'url' is not mapped"))?;
+    let mut hasher = Sha1::new();
+    hasher.update(format!("{}{}", url, Utc::now().to_rfc3339()));
+
+    Ok(Content {
+        id: format!("{:x}", hasher.finalize()),
+        url,
+        source_id,
+        title,
+        body,
+        extra,
+        published_at,
+        created_at: Utc::now(),
+    })
+}
+
+// ValueType::DateTimeでないselectorの場合はRFC 3339として読む
+fn to_datetime(value: &Value) -> Result<DateTime<Utc>> {
+    match value {
+        Value::DateTime(dt) => Ok(dt.with_timezone(&Utc)),
+        other => Ok(DateTime::parse_from_rfc3339(&other.to_string())
+            .map_err(|_| anyhow!("'{}' is not a datetime", other))?
+            .with_timezone(&Utc)),
+    }
 }
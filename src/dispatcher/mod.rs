@@ -1,64 +1,447 @@
+#[cfg(test)]
+mod test;
+
 mod datastore;
 mod job;
+mod query;
+mod schedule;
 
-pub use datastore::DataStoreAdapter;
+pub use datastore::{connect, DataStoreAdapter, MemoryDataStore, PgDataStoreAdapter};
+pub(crate) use query::search_terms;
+pub use query::{ContentCursor, ContentFilter, ContentPage};
+pub use schedule::Schedule;
 
-use crate::crawler::SelectorTree;
+use crate::crawler::{FetchClient, SelectorTree, WebFetcher};
 use crate::dispatcher::job::kick;
-use crate::entity::{Content, Source};
+use crate::entity::{Content, ContentRevision, JobRun, JobStatus, SearchHit, Source};
 use anyhow::Result;
 use async_trait::async_trait;
-use chrono::Duration;
-use futures::future::join_all;
+use chrono::{DateTime, Duration, Utc};
+use futures::stream::{self, StreamExt};
 use log;
+use reqwest::Url;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Mutex as AsyncMutex;
 
 #[async_trait]
 pub trait DataStore {
-    // offset期間以上に更新されていない古いsourcesをすべて取得する
-    async fn get_stale_sources(&self, offset: Duration) -> Result<Vec<Source>>;
+    // enabledでnext_runを過ぎたsourcesをすべて取得する
+    async fn get_stale_sources(&self) -> Result<Vec<Source>>;
     // jobの結果をDatastoreに保存する
+    // 既に保存済みのurlでtitleかbodyが変わっていれば、変更前の内容をrevisionとして残して更新する
+    // accessed_urlsはsourceのcrawl済みのurlに加え、既にあればlast_accessedを更新する
+    // sourceのnext_runはscheduleから計算し直し、連続失敗回数を0に戻す。新しく保存したcontentsの件数を返す
     async fn commit_job_result(
         &self,
         source_id: i32,
         contents: Vec<Content>,
         accessed_urls: Vec<String>,
+    ) -> Result<usize>;
+    // すべてのsourcesを取得する
+    async fn get_sources(&self) -> Result<Vec<Source>>;
+    // idのsourceを取得する。存在しなければNone
+    async fn get_source(&self, source_id: i32) -> Result<Option<Source>>;
+    // sourceの新規作成。作成したsourceのidを返す
+    async fn add_source(&self, selector_tree: SelectorTree) -> Result<i32>;
+    // sourceのselectorsとurlを置き換える。名前はrename_sourceでしか変えない。存在しなければfalse
+    async fn update_source(&self, source_id: i32, selector_tree: SelectorTree) -> Result<bool>;
+    // sourceの名前を変える。selectorsはそのまま。存在しなければfalse
+    async fn rename_source(&self, source_id: i32, name: String) -> Result<bool>;
+    // sourceのscheduleを変え、next_runを前回のcrawlから計算し直す。存在しなければfalse
+    async fn set_schedule(&self, source_id: i32, schedule: Schedule, enabled: bool)
+        -> Result<bool>;
+    // sourceを有効/無効にする。有効にするときは連続失敗回数を0に戻してすぐにcrawlする
+    // 存在しなければfalse
+    async fn set_enabled(&self, source_id: i32, enabled: bool) -> Result<bool>;
+    // jobの失敗を記録する。連続失敗回数を1増やしてnext_runを遅らせ、disableならenabledをfalseにする
+    async fn record_failure(
+        &self,
+        source_id: i32,
+        next_run: DateTime<Utc>,
+        disable: bool,
     ) -> Result<()>;
-    // sourceの新規作成
-    async fn add_source(&self, selector_tree: SelectorTree) -> Result<()>;
+    // sourceをcontents, revisions, job_runsごと削除する。存在しなければfalse
+    async fn remove_source(&self, source_id: i32) -> Result<bool>;
+    // since以降に変更されたcontentsのrevisionを取得する。source_idを指定するとそのsourceに絞る
+    async fn get_revisions_since(
+        &self,
+        source_id: Option<i32>,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<ContentRevision>>;
+    // filterに合うcontentsを新しい順にlimit件取得する。cursorを指定するとその続きから取得する
+    async fn query_contents(
+        &self,
+        filter: ContentFilter,
+        cursor: Option<ContentCursor>,
+        limit: i64,
+    ) -> Result<ContentPage>;
+    // filterに合うcontentsの件数
+    async fn count_contents(&self, filter: ContentFilter) -> Result<i64>;
+    // queryを空白で区切ったすべての語をtitleかbodyに含み、filterに合うcontentsを
+    // よく合う順にoffset件目からlimit件取得する。3文字未満の語はエラーにする
+    async fn search(
+        &self,
+        query: String,
+        filter: ContentFilter,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<SearchHit>>;
+    // sourceでsince以降にcrawlしたurlを取得する。次のcrawlではこれらをskipする
+    async fn get_accessed_urls(&self, source_id: i32, since: DateTime<Utc>) -> Result<Vec<String>>;
+    // jobの実行結果を記録する
+    async fn add_job_run(&self, job_run: JobRun) -> Result<()>;
+    // sourceのjobの実行結果を新しい順にlimit件取得する
+    async fn get_job_runs(&self, source_id: i32, limit: i64) -> Result<Vec<JobRun>>;
+}
+
+// connect()で選んだDataStoreをそのままDispatcherに渡せるようにする
+#[async_trait]
+impl DataStore for Box<dyn DataStore + Send + Sync> {
+    async fn get_stale_sources(&self) -> Result<Vec<Source>> {
+        (**self).get_stale_sources().await
+    }
+
+    async fn commit_job_result(
+        &self,
+        source_id: i32,
+        contents: Vec<Content>,
+        accessed_urls: Vec<String>,
+    ) -> Result<usize> {
+        (**self)
+            .commit_job_result(source_id, contents, accessed_urls)
+            .await
+    }
+
+    async fn get_sources(&self) -> Result<Vec<Source>> {
+        (**self).get_sources().await
+    }
+
+    async fn get_source(&self, source_id: i32) -> Result<Option<Source>> {
+        (**self).get_source(source_id).await
+    }
+
+    async fn add_source(&self, selector_tree: SelectorTree) -> Result<i32> {
+        (**self).add_source(selector_tree).await
+    }
+
+    async fn update_source(&self, source_id: i32, selector_tree: SelectorTree) -> Result<bool> {
+        (**self).update_source(source_id, selector_tree).await
+    }
+
+    async fn rename_source(&self, source_id: i32, name: String) -> Result<bool> {
+        (**self).rename_source(source_id, name).await
+    }
+
+    async fn set_schedule(
+        &self,
+        source_id: i32,
+        schedule: Schedule,
+        enabled: bool,
+    ) -> Result<bool> {
+        (**self).set_schedule(source_id, schedule, enabled).await
+    }
+
+    async fn set_enabled(&self, source_id: i32, enabled: bool) -> Result<bool> {
+        (**self).set_enabled(source_id, enabled).await
+    }
+
+    async fn record_failure(
+        &self,
+        source_id: i32,
+        next_run: DateTime<Utc>,
+        disable: bool,
+    ) -> Result<()> {
+        (**self).record_failure(source_id, next_run, disable).await
+    }
+
+    async fn remove_source(&self, source_id: i32) -> Result<bool> {
+        (**self).remove_source(source_id).await
+    }
+
+    async fn get_revisions_since(
+        &self,
+        source_id: Option<i32>,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<ContentRevision>> {
+        (**self).get_revisions_since(source_id, since).await
+    }
+
+    async fn query_contents(
+        &self,
+        filter: ContentFilter,
+        cursor: Option<ContentCursor>,
+        limit: i64,
+    ) -> Result<ContentPage> {
+        (**self).query_contents(filter, cursor, limit).await
+    }
+
+    async fn count_contents(&self, filter: ContentFilter) -> Result<i64> {
+        (**self).count_contents(filter).await
+    }
+
+    async fn search(
+        &self,
+        query: String,
+        filter: ContentFilter,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<SearchHit>> {
+        (**self).search(query, filter, limit, offset).await
+    }
+
+    async fn get_accessed_urls(&self, source_id: i32, since: DateTime<Utc>) -> Result<Vec<String>> {
+        (**self).get_accessed_urls(source_id, since).await
+    }
+
+    async fn add_job_run(&self, job_run: JobRun) -> Result<()> {
+        (**self).add_job_run(job_run).await
+    }
+
+    async fn get_job_runs(&self, source_id: i32, limit: i64) -> Result<Vec<JobRun>> {
+        (**self).get_job_runs(source_id, limit).await
+    }
 }
 
-pub struct Dispatcher<D: DataStore> {
+const DEFAULT_CONCURRENCY: usize = 8;
+const DEFAULT_MAX_FAILURES: i32 = 5;
+// crawlしたurlはこの期間skipし、過ぎたら取得し直して変更をrevisionとして残す
+const DEFAULT_RECHECK_SECONDS: i64 = 7 * 24 * 60 * 60;
+// 失敗したjobは1分, 2分, 4分...後にやり直す。最大で1日
+const BASE_BACKOFF_SECONDS: i64 = 60;
+const MAX_BACKOFF_SECONDS: i64 = 24 * 60 * 60;
+
+pub struct Dispatcher<D: DataStore, F: FetchClient = WebFetcher> {
     data_store: D,
+    // jobごとに新しいfetcherを作る
+    new_fetcher: Box<dyn Fn() -> F + Send + Sync>,
+    // 同時に実行するjobの上限
+    concurrency: usize,
+    // この回数連続で失敗したsourceは無効にする
+    max_failures: i32,
+    // crawlしたurlを取得し直すまでの期間
+    recheck_interval: Duration,
+    // hostごとのlock。同じhostのsourcesは同時にcrawlしない
+    host_locks: Mutex<HashMap<String, Arc<AsyncMutex<()>>>>,
 }
 
 impl<D: DataStore> Dispatcher<D> {
     pub fn new(data_store: D) -> Self {
-        Dispatcher { data_store }
+        Dispatcher::with_fetcher(data_store, WebFetcher::new)
     }
+}
 
-    pub async fn start(&self, offset: Duration) -> Result<()> {
-        let sources = self.data_store.get_stale_sources(offset).await?;
-        let mut jobs = vec![];
-        for source in sources {
-            let x = kick(source);
-            jobs.push(x);
+impl<D: DataStore, F: 'static + FetchClient + Send> Dispatcher<D, F> {
+    // WebFetcherの代わりにnew_fetcherが作るfetcherでcrawlする
+    pub fn with_fetcher<N>(data_store: D, new_fetcher: N) -> Self
+    where
+        N: Fn() -> F + Send + Sync + 'static,
+    {
+        Dispatcher {
+            data_store,
+            new_fetcher: Box::new(new_fetcher),
+            concurrency: DEFAULT_CONCURRENCY,
+            max_failures: DEFAULT_MAX_FAILURES,
+            recheck_interval: Duration::seconds(DEFAULT_RECHECK_SECONDS),
+            host_locks: Mutex::new(HashMap::new()),
         }
-        let results = join_all(jobs).await;
+    }
 
-        for result in results {
-            if let Ok((source_id, contents, accessed_urls)) = result {
-                let result = self
-                    .data_store
-                    .commit_job_result(source_id, contents, accessed_urls)
-                    .await;
-                if let Err(err) = result {
-                    log::error!("failed to store job result: {:?}", err)
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    pub fn with_max_failures(mut self, max_failures: i32) -> Self {
+        self.max_failures = max_failures.max(1);
+        self
+    }
+
+    pub fn with_recheck_interval(mut self, recheck_interval: Duration) -> Self {
+        self.recheck_interval = recheck_interval.max(Duration::zero());
+        self
+    }
+
+    // 古いsourcesのjobを最大concurrency個ずつ実行し、終わったものから保存する
+    // 同じhostのsourcesは1つの枠で順に実行し、hostの空きを待つjobで枠を埋めないようにする
+    // hostごとに終わった順にjobの実行結果を返す。記録できなかったjobはログに出して除く
+    pub async fn start(&self) -> Result<Vec<JobRun>> {
+        let sources = self.data_store.get_stale_sources().await?;
+        let job_runs = stream::iter(group_by_host(sources))
+            .map(|sources| async move {
+                let mut job_runs = vec![];
+                for source in sources {
+                    let source_id = source.id;
+                    match self.run(source).await {
+                        Ok(job_run) => job_runs.push(job_run),
+                        Err(err) => {
+                            log::error!("failed to record job of source {}: {:?}", source_id, err)
+                        }
+                    }
+                }
+                job_runs
+            })
+            .buffer_unordered(self.concurrency)
+            .flat_map(stream::iter)
+            .collect()
+            .await;
+
+        Ok(job_runs)
+    }
+
+    // poll間隔ごとに古いsourcesのjobを実行し続ける
+    // shutdownが完了したら、実行中のjobの完了を待ってから終了する
+    pub async fn run_forever<S>(&self, poll: std::time::Duration, shutdown: S)
+    where
+        S: Future<Output = ()>,
+    {
+        tokio::pin!(shutdown);
+        let mut interval = tokio::time::interval(poll);
+        loop {
+            tokio::select! {
+                _ = &mut shutdown => break,
+                _ = interval.tick() => {
+                    if let Err(err) = self.start().await {
+                        log::error!("failed to get stale sources: {:?}", err)
+                    }
                 }
-            } else {
-                log::error!("failed to execute job: {:?}", result.unwrap_err())
             }
         }
+    }
 
-        Ok(())
+    // sourceを今すぐcrawlして結果を保存し、実行結果をjob_runsに記録する
+    // jobの失敗はJobStatus::Failedとして返し、記録できなかったときだけエラーにする
+    pub async fn run(&self, source: Source) -> Result<JobRun> {
+        let host = host_of(&source.url);
+        let lock = self.host_lock(&host);
+        let guard = lock.lock().await;
+        let result = self.run_locked(source).await;
+        drop(guard);
+        drop(lock);
+        self.release_host_lock(&host);
+        result
     }
+
+    // hostのlockを取ってから呼ぶ
+    async fn run_locked(&self, source: Source) -> Result<JobRun> {
+        let source_id = source.id;
+        let schedule = source.schedule.clone();
+        let failures = source.consecutive_failures + 1;
+        let started_at = Utc::now();
+        let (pages_fetched, result) = self.execute(source).await;
+        let finished_at = Utc::now();
+
+        let job_run = match result {
+            Ok(contents_inserted) => JobRun {
+                source_id,
+                started_at,
+                finished_at,
+                status: JobStatus::Succeeded,
+                pages_fetched: pages_fetched as i32,
+                contents_inserted: contents_inserted as i32,
+                error: None,
+            },
+            Err(err) => {
+                log::error!("failed to execute job of source {}: {:?}", source_id, err);
+                let disable = failures >= self.max_failures;
+                if disable {
+                    log::warn!("source {}: disabled after {} failures", source_id, failures);
+                }
+                // scheduleの次の実行より早くはやり直さない
+                // scheduleが不正ならそれも失敗として数え、backoffだけ待つ
+                let retry_at = finished_at + backoff(failures);
+                let next_run = match schedule.next_after(finished_at) {
+                    Ok(next_run) => next_run.max(retry_at),
+                    Err(_) => retry_at,
+                };
+                self.data_store
+                    .record_failure(source_id, next_run, disable)
+                    .await?;
+                JobRun {
+                    source_id,
+                    started_at,
+                    finished_at,
+                    status: JobStatus::Failed,
+                    pages_fetched: pages_fetched as i32,
+                    contents_inserted: 0,
+                    error: Some(format!("{:#}", err)),
+                }
+            }
+        };
+        self.data_store.add_job_run(job_run.clone()).await?;
+
+        Ok(job_run)
+    }
+
+    // 取得したページ数と新しく保存したcontentsの件数を返す。失敗してもページ数は返す
+    async fn execute(&self, source: Source) -> (usize, Result<usize>) {
+        let source_id = source.id;
+        let since = Utc::now() - self.recheck_interval;
+        let skip_urls = match self.data_store.get_accessed_urls(source_id, since).await {
+            Ok(skip_urls) => skip_urls,
+            Err(err) => return (0, Err(err)),
+        };
+        let (pages_fetched, result) = kick((self.new_fetcher)(), source, skip_urls).await;
+        let result = match result {
+            Ok((contents, accessed_urls)) => {
+                self.data_store
+                    .commit_job_result(source_id, contents, accessed_urls)
+                    .await
+            }
+            Err(err) => Err(err),
+        };
+        (pages_fetched, result)
+    }
+
+    pub fn data_store(&self) -> &D {
+        &self.data_store
+    }
+
+    fn host_lock(&self, host: &str) -> Arc<AsyncMutex<()>> {
+        let mut host_locks = self.host_locks.lock().unwrap();
+        Arc::clone(host_locks.entry(host.to_string()).or_default())
+    }
+
+    // 他に使っているjobがなければhostのlockを消す
+    fn release_host_lock(&self, host: &str) {
+        let mut host_locks = self.host_locks.lock().unwrap();
+        if host_locks
+            .get(host)
+            .is_some_and(|lock| Arc::strong_count(lock) == 1)
+        {
+            host_locks.remove(host);
+        }
+    }
+}
+
+// failures回連続で失敗したsourceを次にcrawlするまでの時間
+fn backoff(failures: i32) -> Duration {
+    let exponent = (failures - 1).clamp(0, 20) as u32;
+    Duration::seconds((BASE_BACKOFF_SECONDS << exponent).min(MAX_BACKOFF_SECONDS))
+}
+
+// sourcesをhostごとにまとめる。hostもsourcesも元の順に並べる
+fn group_by_host(sources: Vec<Source>) -> Vec<Vec<Source>> {
+    let mut groups: Vec<Vec<Source>> = vec![];
+    let mut indexes: HashMap<String, usize> = HashMap::new();
+    for source in sources {
+        let host = host_of(&source.url);
+        match indexes.get(&host) {
+            Some(&index) => groups[index].push(source),
+            None => {
+                indexes.insert(host, groups.len());
+                groups.push(vec![source]);
+            }
+        }
+    }
+    groups
+}
+
+// urlが読めなければurlそのものをhostとみなす
+fn host_of(url: &str) -> String {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_else(|| url.to_string())
 }
@@ -0,0 +1,225 @@
+use crate::entity::Content;
+use anyhow::{anyhow, Error, Result};
+use chrono::{DateTime, TimeZone, Utc};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+// contentsの絞り込み条件。Noneの条件では絞り込まない
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ContentFilter {
+    pub source_id: Option<i32>,
+    // created_atがsince以降until未満のもの
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    // titleかbodyにkeywordを含むもの。大文字小文字は区別しない
+    pub keyword: Option<String>,
+}
+
+impl ContentFilter {
+    pub fn source(source_id: i32) -> Self {
+        ContentFilter {
+            source_id: Some(source_id),
+            ..ContentFilter::default()
+        }
+    }
+
+    // データベースを使わないDataStoreのための絞り込み
+    pub(crate) fn matches(&self, content: &Content) -> bool {
+        self.source_id.is_none_or(|id| id == content.source_id)
+            && self.since.is_none_or(|since| content.created_at >= since)
+            && self.until.is_none_or(|until| content.created_at < until)
+            && self.keyword.as_ref().is_none_or(|keyword| {
+                let keyword = keyword.to_lowercase();
+                content.title.to_lowercase().contains(&keyword)
+                    || content.body.to_lowercase().contains(&keyword)
+            })
+    }
+
+    // LIKEで使うkeywordのpattern。%と_はそのままの文字として扱うので'\'でescapeする
+    pub(crate) fn like_pattern(&self) -> Option<String> {
+        self.keyword.as_ref().map(|keyword| {
+            let escaped = keyword
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+            format!("%{}%", escaped)
+        })
+    }
+}
+
+// contentsは新しい順(created_atの降順、同じならidの昇順)に並べる
+// cursorは前のページの最後のcontentで、次のページはその直後から始まる
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContentCursor {
+    pub created_at: DateTime<Utc>,
+    pub id: String,
+}
+
+impl ContentCursor {
+    pub fn of(content: &Content) -> Self {
+        ContentCursor {
+            created_at: content.created_at,
+            id: content.id.clone(),
+        }
+    }
+
+    // contentがcursorより後に並ぶならtrue
+    pub(crate) fn precedes(&self, content: &Content) -> bool {
+        order(content.created_at, &content.id, self.created_at, &self.id) == Ordering::Greater
+    }
+}
+
+// ex: 1596240000000000_8f14e45f
+impl fmt::Display for ContentCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}_{}", self.created_at.timestamp_micros(), self.id)
+    }
+}
+
+impl FromStr for ContentCursor {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || anyhow!("invalid cursor: {}", s);
+        let (micros, id) = s.split_once('_').ok_or_else(invalid)?;
+        let created_at = Utc
+            .timestamp_micros(micros.parse().map_err(|_| invalid())?)
+            .single()
+            .ok_or_else(invalid)?;
+        Ok(ContentCursor {
+            created_at,
+            id: id.to_string(),
+        })
+    }
+}
+
+// contentsの並び順での比較
+pub(crate) fn order(
+    created_at: DateTime<Utc>,
+    id: &str,
+    other_created_at: DateTime<Utc>,
+    other_id: &str,
+) -> Ordering {
+    other_created_at
+        .cmp(&created_at)
+        .then_with(|| id.cmp(other_id))
+}
+
+#[derive(Debug)]
+pub struct ContentPage {
+    pub items: Vec<Content>,
+    // 続きがあれば次のページのcursor。最後のページならNone
+    pub next_cursor: Option<ContentCursor>,
+}
+
+impl ContentPage {
+    // limit + 1件取得したcontentsから、limit件のページを作る
+    pub(crate) fn new(mut items: Vec<Content>, limit: i64) -> Self {
+        let limit = limit.max(0) as usize;
+        let next_cursor = if items.len() > limit {
+            items.truncate(limit);
+            items.last().map(ContentCursor::of)
+        } else {
+            None
+        };
+        ContentPage { items, next_cursor }
+    }
+}
+
+// 全文検索で合った部分を囲む印
+pub(crate) const MARK_START: &str = "<mark>";
+pub(crate) const MARK_END: &str = "</mark>";
+// 全文検索のsnippetの長さ(文字数)
+pub(crate) const SNIPPET_CHARS: usize = 64;
+// SQLiteのtrigramは3文字未満の語を探せないので、どのDataStoreでも同じくエラーにする
+const MIN_TERM_CHARS: usize = 3;
+
+// 全文検索のqueryを空白で区切った語。すべての語を含むcontentsを探す
+pub(crate) fn search_terms(query: &str) -> Result<Vec<String>> {
+    let terms = query
+        .split_whitespace()
+        .map(str::to_string)
+        .collect::<Vec<String>>();
+    if terms.is_empty() {
+        return Err(anyhow!("search query is empty"));
+    }
+    if let Some(term) = terms
+        .iter()
+        .find(|term| term.chars().count() < MIN_TERM_CHARS)
+    {
+        return Err(anyhow!(
+            "search term must be at least {} characters: {}",
+            MIN_TERM_CHARS,
+            term
+        ));
+    }
+    Ok(terms)
+}
+
+// FTS5のMATCHに渡すquery。それぞれの語を"で囲み、FTS5の演算子として扱わないようにする
+pub(crate) fn fts_query(terms: &[String]) -> String {
+    terms
+        .iter()
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// textのうちtermsに合う部分を印で囲む。大文字小文字はASCIIだけ区別しない
+pub(crate) fn highlight(text: &str, terms: &[String]) -> String {
+    let lower = text.to_ascii_lowercase();
+    let terms = terms
+        .iter()
+        .map(|term| term.to_ascii_lowercase())
+        .collect::<Vec<String>>();
+
+    let mut highlighted = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < text.len() {
+        match terms
+            .iter()
+            .find(|term| lower[i..].starts_with(term.as_str()))
+        {
+            Some(term) => {
+                highlighted.push_str(MARK_START);
+                highlighted.push_str(&text[i..i + term.len()]);
+                highlighted.push_str(MARK_END);
+                i += term.len();
+            }
+            None => {
+                let c = text[i..].chars().next().unwrap();
+                highlighted.push(c);
+                i += c.len_utf8();
+            }
+        }
+    }
+    highlighted
+}
+
+// textのうち最初にtermsに合う部分の前後SNIPPET_CHARS文字。切り取った側には…を付ける
+pub(crate) fn snippet(text: &str, terms: &[String]) -> String {
+    let lower = text.to_ascii_lowercase();
+    let first = terms
+        .iter()
+        .filter_map(|term| lower.find(&term.to_ascii_lowercase()))
+        .min()
+        .unwrap_or(0);
+
+    let offsets = text
+        .char_indices()
+        .map(|(offset, _)| offset)
+        .collect::<Vec<usize>>();
+    let first_char = offsets.iter().take_while(|offset| **offset < first).count();
+    let start = first_char.saturating_sub(SNIPPET_CHARS / 4);
+    let end = (start + SNIPPET_CHARS).min(offsets.len());
+    let start_offset = offsets.get(start).copied().unwrap_or(text.len());
+    let end_offset = offsets.get(end).copied().unwrap_or(text.len());
+
+    format!(
+        "{}{}{}",
+        if start > 0 { "…" } else { "" },
+        highlight(&text[start_offset..end_offset], terms),
+        if end < offsets.len() { "…" } else { "" }
+    )
+}
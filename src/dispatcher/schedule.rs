@@ -0,0 +1,50 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+// sourceをcrawlする予定
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Schedule {
+    // seconds秒ごと
+    Interval {
+        seconds: i32,
+    },
+    // 秒から始まるcron式 (ex: "0 0 9 * * *")。timezoneはIANAの名前 (ex: "Asia/Tokyo")
+    Cron {
+        expression: String,
+        timezone: String,
+    },
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Schedule::Interval { seconds: 3600 }
+    }
+}
+
+impl Schedule {
+    // afterより後で次にcrawlする時刻。式やtimezoneが不正ならエラー
+    pub fn next_after(&self, after: DateTime<Utc>) -> Result<DateTime<Utc>> {
+        match self {
+            Schedule::Interval { seconds } if *seconds <= 0 => {
+                Err(anyhow!("interval must be positive: {}", seconds))
+            }
+            Schedule::Interval { seconds } => Ok(after + Duration::seconds(*seconds as i64)),
+            Schedule::Cron {
+                expression,
+                timezone,
+            } => {
+                let tz = Tz::from_str(timezone).map_err(|err| anyhow!(err))?;
+                cron::Schedule::from_str(expression)
+                    .map_err(|err| anyhow!("invalid cron expression '{}': {}", expression, err))?
+                    .after(&after.with_timezone(&tz))
+                    .next()
+                    .map(|next| next.with_timezone(&Utc))
+                    .ok_or_else(|| anyhow!("cron expression '{}' never fires", expression))
+            }
+        }
+    }
+}
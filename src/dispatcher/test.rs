@@ -0,0 +1,796 @@
+use crate::crawler::{FetchClient, SelectorTree};
+use crate::dispatcher::{
+    backoff, host_of, ContentCursor, ContentFilter, DataStore, Dispatcher, MemoryDataStore,
+    Schedule,
+};
+use crate::entity::{Content, JobStatus};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::{Duration, TimeZone, Utc};
+use serde_json::Map;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio;
+
+// mapping Url -> Html, shared by the fetchers of all jobs. every fetch waits a little, so that
+// jobs running at the same time overlap
+#[derive(Clone, Default)]
+struct MockedFetcher {
+    pages: Arc<Mutex<HashMap<String, String>>>,
+    stats: Arc<Mutex<FetchStats>>,
+    access_logs: Vec<String>,
+}
+
+#[derive(Default)]
+struct FetchStats {
+    fetched: Vec<String>,
+    in_flight: HashMap<String, usize>,
+    // the max number of fetches at the same time, for each host and for all hosts
+    max_in_flight_per_host: usize,
+    max_in_flight: usize,
+}
+
+impl MockedFetcher {
+    fn new(pages: Vec<(&str, &str)>) -> Self {
+        MockedFetcher {
+            pages: Arc::new(Mutex::new(
+                pages
+                    .into_iter()
+                    .map(|(url, html)| (url.to_string(), html.to_string()))
+                    .collect(),
+            )),
+            ..MockedFetcher::default()
+        }
+    }
+
+    // changes the page for the fetchers made from this one
+    fn set_page(&self, url: &str, html: &str) {
+        self.pages
+            .lock()
+            .unwrap()
+            .insert(url.to_string(), html.to_string());
+    }
+
+    fn fetched(&self) -> Vec<String> {
+        self.stats.lock().unwrap().fetched.clone()
+    }
+}
+
+#[async_trait]
+impl FetchClient for MockedFetcher {
+    async fn fetch(&mut self, url: &String, logging: bool) -> Result<String> {
+        let host = host_of(url);
+        {
+            let mut stats = self.stats.lock().unwrap();
+            stats.fetched.push(url.clone());
+            *stats.in_flight.entry(host.clone()).or_default() += 1;
+            let per_host = stats.in_flight.values().copied().max().unwrap_or(0);
+            let total = stats.in_flight.values().sum();
+            stats.max_in_flight_per_host = stats.max_in_flight_per_host.max(per_host);
+            stats.max_in_flight = stats.max_in_flight.max(total);
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        *self.stats.lock().unwrap().in_flight.get_mut(&host).unwrap() -= 1;
+
+        let html = self
+            .pages
+            .lock()
+            .unwrap()
+            .get(url)
+            .cloned()
+            .ok_or_else(|| anyhow!("html not found by the url: {}", url))?;
+        if logging {
+            self.access_logs.push(url.clone());
+        }
+        Ok(html)
+    }
+
+    fn dump_access_logs(self) -> Vec<String> {
+        self.access_logs
+    }
+}
+
+fn sitemap(id: &str, start_url: &str) -> SelectorTree {
+    SelectorTree::new(format!(
+        r#"{{
+  "_id": "{}",
+  "startUrl": ["{}"],
+  "selectors": [
+    {{"id": "link", "type": "SelectorLink", "parentSelectors": ["_root"], "selector": ".url", "multiple": true, "delay": 0}},
+    {{"id": "title", "type": "SelectorText", "parentSelectors": ["link"], "selector": ".title", "multiple": false, "delay": 0}},
+    {{"id": "body", "type": "SelectorText", "parentSelectors": ["link"], "selector": ".body", "multiple": true, "delay": 0}}
+  ]
+}}"#,
+        id, start_url
+    ))
+    .unwrap()
+}
+
+fn pages() -> Vec<(&'static str, &'static str)> {
+    vec![
+        (
+            "https://formula1-data.com/article",
+            r#"<a class="url" href="https://formula1-data.com/article/1">1</a>
+               <a class="url" href="https://formula1-data.com/article/2">2</a>"#,
+        ),
+        (
+            "https://formula1-data.com/article/1",
+            r#"<p class="title">title 1</p><p class="body">body 1</p>"#,
+        ),
+        (
+            "https://formula1-data.com/article/2",
+            r#"<p class="title">title 2</p><p class="body">body 2</p>"#,
+        ),
+        (
+            "https://f1-news.com/article",
+            r#"<a class="url" href="https://f1-news.com/article/1">1</a>"#,
+        ),
+        (
+            "https://f1-news.com/article/1",
+            r#"<p class="title">news 1</p><p class="body">news body 1</p>"#,
+        ),
+    ]
+}
+
+fn content(source_id: i32, url: &str, title: &str, body: &str) -> Content {
+    Content {
+        id: format!("{}{}", url, title),
+        url: url.to_string(),
+        source_id,
+        title: title.to_string(),
+        body: body.to_string(),
+        extra: Map::new(),
+        published_at: None,
+        created_at: Utc::now(),
+    }
+}
+
+#[tokio::test]
+async fn dispatcher_start_test() {
+    let fetcher = MockedFetcher::new(pages());
+    let dispatcher = {
+        let fetcher = fetcher.clone();
+        Dispatcher::with_fetcher(MemoryDataStore::new(), move || fetcher.clone())
+    };
+    let data_store = dispatcher.data_store();
+    let source_id = data_store
+        .add_source(sitemap(
+            "formula1-data",
+            "https://formula1-data.com/article",
+        ))
+        .await
+        .unwrap();
+    let disabled_id = data_store
+        .add_source(sitemap("f1-news", "https://f1-news.com/article"))
+        .await
+        .unwrap();
+    data_store.set_enabled(disabled_id, false).await.unwrap();
+
+    let job_runs = dispatcher.start().await.unwrap();
+    assert_eq!(job_runs.len(), 1);
+    assert_eq!(job_runs[0].source_id, source_id);
+
+    let contents = data_store
+        .query_contents(ContentFilter::default(), None, 10)
+        .await
+        .unwrap()
+        .items;
+    let mut titles = contents
+        .iter()
+        .map(|content| content.title.as_str())
+        .collect::<Vec<&str>>();
+    titles.sort();
+    assert_eq!(titles, vec!["title 1", "title 2"]);
+
+    let job_runs = data_store.get_job_runs(source_id, 10).await.unwrap();
+    assert_eq!(job_runs.len(), 1);
+    assert_eq!(job_runs[0].status, JobStatus::Succeeded);
+    // the start url and 2 articles
+    assert_eq!(job_runs[0].pages_fetched, 3);
+    assert_eq!(job_runs[0].contents_inserted, 2);
+    assert!(data_store
+        .get_job_runs(disabled_id, 10)
+        .await
+        .unwrap()
+        .is_empty());
+
+    assert_eq!(
+        data_store
+            .get_accessed_urls(source_id, job_runs[0].started_at)
+            .await
+            .unwrap(),
+        vec![
+            "https://formula1-data.com/article/1".to_string(),
+            "https://formula1-data.com/article/2".to_string(),
+        ]
+    );
+    let source = data_store.get_source(source_id).await.unwrap().unwrap();
+    assert!(source.next_run > Utc::now() + Duration::minutes(59));
+
+    // the source is not stale until the next run
+    assert!(data_store.get_stale_sources().await.unwrap().is_empty());
+    let fetched = fetcher.fetched().len();
+    assert!(dispatcher.start().await.unwrap().is_empty());
+    assert_eq!(fetcher.fetched().len(), fetched);
+
+    // the pages accessed within the recheck interval are skipped on the next crawl
+    let job_run = dispatcher.run(source).await.unwrap();
+    assert_eq!(job_run.status, JobStatus::Succeeded);
+    assert_eq!(job_run.pages_fetched, 1);
+    assert_eq!(job_run.contents_inserted, 0);
+    assert_eq!(
+        fetcher.fetched()[fetched..],
+        ["https://formula1-data.com/article".to_string()]
+    );
+}
+
+#[tokio::test]
+async fn dispatcher_recheck_test() {
+    let fetcher = MockedFetcher::new(pages());
+    let dispatcher = {
+        let fetcher = fetcher.clone();
+        Dispatcher::with_fetcher(MemoryDataStore::new(), move || fetcher.clone())
+            .with_recheck_interval(Duration::zero())
+    };
+    let data_store = dispatcher.data_store();
+    let source_id = data_store
+        .add_source(sitemap(
+            "formula1-data",
+            "https://formula1-data.com/article",
+        ))
+        .await
+        .unwrap();
+    let since = Utc::now();
+    let source = data_store.get_source(source_id).await.unwrap().unwrap();
+    dispatcher.run(source).await.unwrap();
+
+    // the pages accessed before the recheck interval are fetched again, and a changed article
+    // leaves a revision
+    fetcher.set_page(
+        "https://formula1-data.com/article/1",
+        r#"<p class="title">title 1</p><p class="body">body 1 updated</p>"#,
+    );
+    let source = data_store.get_source(source_id).await.unwrap().unwrap();
+    let job_run = dispatcher.run(source).await.unwrap();
+    assert_eq!(job_run.status, JobStatus::Succeeded);
+    assert_eq!(job_run.pages_fetched, 3);
+    assert_eq!(job_run.contents_inserted, 0);
+
+    let revisions = data_store
+        .get_revisions_since(Some(source_id), since)
+        .await
+        .unwrap();
+    assert_eq!(revisions.len(), 1);
+    assert_eq!(revisions[0].url, "https://formula1-data.com/article/1");
+    assert_eq!(revisions[0].body, "body 1");
+    assert!(revisions[0].diff.contains("+body 1 updated"));
+    let contents = data_store
+        .query_contents(ContentFilter::default(), None, 10)
+        .await
+        .unwrap()
+        .items;
+    assert_eq!(contents.len(), 2);
+    assert!(contents
+        .iter()
+        .any(|content| content.body == "body 1 updated"));
+}
+
+#[tokio::test]
+async fn dispatcher_failure_test() {
+    // the start url only, so the links fail
+    let dispatcher = Dispatcher::with_fetcher(MemoryDataStore::new(), || {
+        MockedFetcher::new(pages()[..1].to_vec())
+    })
+    .with_max_failures(2);
+    let data_store = dispatcher.data_store();
+    let source_id = data_store
+        .add_source(sitemap(
+            "formula1-data",
+            "https://formula1-data.com/article",
+        ))
+        .await
+        .unwrap();
+
+    let source = data_store.get_source(source_id).await.unwrap().unwrap();
+    let job_run = dispatcher.run(source).await.unwrap();
+    assert_eq!(job_run.status, JobStatus::Failed);
+    assert!(job_run.error.unwrap().contains("html not found"));
+    // the pages fetched before the failure are counted
+    assert_eq!(job_run.pages_fetched, 1);
+
+    // retried on the schedule, which is later than the backoff
+    let source = data_store.get_source(source_id).await.unwrap().unwrap();
+    assert_eq!(source.consecutive_failures, 1);
+    assert!(source.enabled);
+    assert_eq!(
+        source.next_run,
+        source.schedule.next_after(job_run.finished_at).unwrap()
+    );
+    assert!(source.next_run > job_run.finished_at + backoff(1));
+    assert!(data_store.get_stale_sources().await.unwrap().is_empty());
+
+    dispatcher.run(source).await.unwrap();
+    let source = data_store.get_source(source_id).await.unwrap().unwrap();
+    assert_eq!(source.consecutive_failures, 2);
+    assert!(!source.enabled);
+
+    // enabling a source resets the failures and crawls it on the next poll
+    data_store.set_enabled(source_id, true).await.unwrap();
+    let stale = data_store.get_stale_sources().await.unwrap();
+    assert_eq!(stale.len(), 1);
+    assert_eq!(stale[0].consecutive_failures, 0);
+
+    assert_eq!(
+        data_store.get_job_runs(source_id, 10).await.unwrap().len(),
+        2
+    );
+    assert!(data_store
+        .query_contents(ContentFilter::default(), None, 10)
+        .await
+        .unwrap()
+        .items
+        .is_empty());
+
+    // a daily source waits for the next day instead of the backoff of minutes
+    let daily = Schedule::Cron {
+        expression: "0 0 9 * * *".to_string(),
+        timezone: "Asia/Tokyo".to_string(),
+    };
+    data_store
+        .set_schedule(source_id, daily.clone(), true)
+        .await
+        .unwrap();
+    let source = data_store.get_source(source_id).await.unwrap().unwrap();
+    let job_run = dispatcher.run(source).await.unwrap();
+    let source = data_store.get_source(source_id).await.unwrap().unwrap();
+    assert_eq!(
+        source.next_run,
+        daily.next_after(job_run.finished_at).unwrap()
+    );
+    assert!(source.enabled);
+
+    // a short interval waits for the backoff
+    data_store
+        .set_schedule(source_id, Schedule::Interval { seconds: 10 }, true)
+        .await
+        .unwrap();
+    let source = data_store.get_source(source_id).await.unwrap().unwrap();
+    let job_run = dispatcher.run(source).await.unwrap();
+    let source = data_store.get_source(source_id).await.unwrap().unwrap();
+    assert_eq!(source.next_run, job_run.finished_at + backoff(2));
+    assert!(!source.enabled);
+}
+
+#[tokio::test]
+async fn dispatcher_host_lock_test() {
+    let fetcher = MockedFetcher::new(pages());
+    let dispatcher = {
+        let fetcher = fetcher.clone();
+        Dispatcher::with_fetcher(MemoryDataStore::new(), move || fetcher.clone())
+    };
+    let data_store = dispatcher.data_store();
+    for (id, url) in [
+        ("formula1-data", "https://formula1-data.com/article"),
+        ("formula1-data-2", "https://formula1-data.com/article"),
+        ("f1-news", "https://f1-news.com/article"),
+    ] {
+        data_store.add_source(sitemap(id, url)).await.unwrap();
+    }
+
+    dispatcher.start().await.unwrap();
+
+    // the sources of the same host are crawled one by one, and the other hosts in parallel
+    {
+        let stats = fetcher.stats.lock().unwrap();
+        assert_eq!(stats.max_in_flight_per_host, 1);
+        assert_eq!(stats.max_in_flight, 2);
+        assert_eq!(stats.fetched.len(), 3 + 3 + 2);
+    }
+    // the locks of the hosts no job uses are removed
+    assert!(dispatcher.host_locks.lock().unwrap().is_empty());
+
+    // the sources waiting for a host do not take the slots of the other hosts
+    let fetcher = MockedFetcher::new(pages());
+    let dispatcher = {
+        let fetcher = fetcher.clone();
+        Dispatcher::with_fetcher(MemoryDataStore::new(), move || fetcher.clone())
+            .with_concurrency(2)
+    };
+    let data_store = dispatcher.data_store();
+    for (id, url) in [
+        ("formula1-data", "https://formula1-data.com/article"),
+        ("formula1-data-2", "https://formula1-data.com/article"),
+        ("formula1-data-3", "https://formula1-data.com/article"),
+        ("f1-news", "https://f1-news.com/article"),
+    ] {
+        data_store.add_source(sitemap(id, url)).await.unwrap();
+    }
+    let job_runs = dispatcher.start().await.unwrap();
+    assert_eq!(job_runs.len(), 4);
+    assert_eq!(job_runs[0].source_id, 4);
+    let stats = fetcher.stats.lock().unwrap();
+    assert_eq!(stats.max_in_flight_per_host, 1);
+    // the first fetches of both hosts are in flight together
+    assert_eq!(
+        stats.fetched[..2]
+            .iter()
+            .map(|url| host_of(url))
+            .collect::<Vec<String>>(),
+        vec!["formula1-data.com", "f1-news.com"]
+    );
+}
+
+#[tokio::test]
+async fn memory_data_store_commit_test() {
+    let data_store = MemoryDataStore::new();
+    let source_id = data_store
+        .add_source(sitemap(
+            "formula1-data",
+            "https://formula1-data.com/article",
+        ))
+        .await
+        .unwrap();
+    let url = "https://formula1-data.com/article/1";
+    let since = Utc::now();
+
+    let inserted = data_store
+        .commit_job_result(
+            source_id,
+            vec![content(source_id, url, "title 1", "body 1")],
+            vec![url.to_string()],
+        )
+        .await
+        .unwrap();
+    assert_eq!(inserted, 1);
+
+    // the same content is not saved twice, and a changed one leaves a revision
+    let inserted = data_store
+        .commit_job_result(
+            source_id,
+            vec![
+                content(source_id, url, "title 1", "body 1"),
+                content(source_id, url, "title 1", "body 1 updated"),
+            ],
+            vec![
+                url.to_string(),
+                "https://formula1-data.com/article?a=1,2".to_string(),
+            ],
+        )
+        .await
+        .unwrap();
+    assert_eq!(inserted, 0);
+    let contents = data_store
+        .query_contents(ContentFilter::source(source_id), None, 10)
+        .await
+        .unwrap()
+        .items;
+    assert_eq!(contents.len(), 1);
+    assert_eq!(contents[0].body, "body 1 updated");
+    let revisions = data_store
+        .get_revisions_since(Some(source_id), since)
+        .await
+        .unwrap();
+    assert_eq!(revisions.len(), 1);
+    assert_eq!(revisions[0].body, "body 1");
+    assert!(revisions[0].diff.contains("+body 1 updated"));
+
+    // accessed urls are added to the ones of the previous jobs
+    assert_eq!(
+        data_store
+            .get_accessed_urls(source_id, since)
+            .await
+            .unwrap(),
+        vec![
+            "https://formula1-data.com/article/1".to_string(),
+            "https://formula1-data.com/article?a=1,2".to_string(),
+        ]
+    );
+    // the urls accessed before since are not skipped
+    assert!(data_store
+        .get_accessed_urls(source_id, Utc::now() + Duration::minutes(1))
+        .await
+        .unwrap()
+        .is_empty());
+
+    // nothing is saved if the source does not exist
+    assert!(data_store
+        .commit_job_result(
+            source_id + 1,
+            vec![content(source_id + 1, url, "title 1", "body 1")],
+            vec![],
+        )
+        .await
+        .is_err());
+    assert_eq!(
+        data_store
+            .query_contents(ContentFilter::default(), None, 10)
+            .await
+            .unwrap()
+            .items
+            .len(),
+        1
+    );
+
+    assert!(data_store.remove_source(source_id).await.unwrap());
+    assert!(data_store
+        .query_contents(ContentFilter::default(), None, 10)
+        .await
+        .unwrap()
+        .items
+        .is_empty());
+    assert!(data_store
+        .get_revisions_since(None, since)
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(data_store
+        .get_accessed_urls(source_id, since)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn schedule_next_after_test() {
+    let after = Utc.with_ymd_and_hms(2020, 8, 1, 12, 30, 0).unwrap();
+
+    let schedule = Schedule::Interval { seconds: 3600 };
+    assert_eq!(
+        schedule.next_after(after).unwrap(),
+        Utc.with_ymd_and_hms(2020, 8, 1, 13, 30, 0).unwrap()
+    );
+
+    // 9:00 in Tokyo is 0:00 in UTC, so the next one is on the next day
+    let schedule = Schedule::Cron {
+        expression: "0 0 9 * * *".to_string(),
+        timezone: "Asia/Tokyo".to_string(),
+    };
+    assert_eq!(
+        schedule.next_after(after).unwrap(),
+        Utc.with_ymd_and_hms(2020, 8, 2, 0, 0, 0).unwrap()
+    );
+
+    let schedule = Schedule::Cron {
+        expression: "0 0 9 * * *".to_string(),
+        timezone: "Mars/Olympus".to_string(),
+    };
+    assert!(schedule.next_after(after).is_err());
+
+    let schedule = Schedule::Cron {
+        expression: "every day".to_string(),
+        timezone: "UTC".to_string(),
+    };
+    assert!(schedule.next_after(after).is_err());
+
+    let schedule = Schedule::Interval { seconds: 0 };
+    assert!(schedule.next_after(after).is_err());
+}
+
+#[test]
+fn schedule_serde_test() {
+    let schedule: Schedule =
+        serde_json::from_str(r#"{"type": "interval", "seconds": 600}"#).unwrap();
+    assert_eq!(schedule, Schedule::Interval { seconds: 600 });
+
+    let schedule: Schedule = serde_json::from_str(
+        r#"{"type": "cron", "expression": "0 0 9 * * *", "timezone": "Asia/Tokyo"}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        schedule,
+        Schedule::Cron {
+            expression: "0 0 9 * * *".to_string(),
+            timezone: "Asia/Tokyo".to_string(),
+        }
+    );
+}
+
+#[test]
+fn host_of_test() {
+    assert_eq!(
+        host_of("https://formula1-data.com/article?page=2"),
+        "formula1-data.com"
+    );
+    assert_eq!(
+        host_of("http://formula1-data.com:8080/"),
+        "formula1-data.com"
+    );
+    assert_eq!(host_of("not a url"), "not a url");
+}
+
+#[test]
+fn backoff_test() {
+    assert_eq!(backoff(1), Duration::minutes(1));
+    assert_eq!(backoff(2), Duration::minutes(2));
+    assert_eq!(backoff(4), Duration::minutes(8));
+    assert_eq!(backoff(12), Duration::days(1));
+    assert_eq!(backoff(1000), Duration::days(1));
+}
+
+#[test]
+fn content_cursor_test() {
+    let cursor = ContentCursor {
+        created_at: Utc.with_ymd_and_hms(2020, 8, 1, 0, 0, 0).unwrap(),
+        id: "8f14e45f".to_string(),
+    };
+    assert_eq!(cursor.to_string(), "1596240000000000_8f14e45f");
+    assert_eq!(cursor.to_string().parse::<ContentCursor>().unwrap(), cursor);
+
+    assert!("8f14e45f".parse::<ContentCursor>().is_err());
+    assert!("yesterday_8f14e45f".parse::<ContentCursor>().is_err());
+
+    let filter = ContentFilter {
+        keyword: Some(r"100%_\".to_string()),
+        ..ContentFilter::default()
+    };
+    assert_eq!(filter.like_pattern().unwrap(), r"%100\%\_\\%");
+}
+
+#[tokio::test]
+async fn memory_data_store_query_test() {
+    let data_store = MemoryDataStore::new();
+    let source_id = data_store
+        .add_source(sitemap(
+            "formula1-data",
+            "https://formula1-data.com/article",
+        ))
+        .await
+        .unwrap();
+    let other_id = data_store
+        .add_source(sitemap("f1-news", "https://f1-news.com/article"))
+        .await
+        .unwrap();
+
+    let contents = (1..=5)
+        .map(|i| {
+            content(
+                source_id,
+                &format!("https://formula1-data.com/article/{}", i),
+                &format!("title {}", i),
+                &format!("body {}", i),
+            )
+        })
+        .collect();
+    data_store
+        .commit_job_result(source_id, contents, vec![])
+        .await
+        .unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    let middle = Utc::now();
+    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    data_store
+        .commit_job_result(
+            other_id,
+            vec![content(
+                other_id,
+                "https://f1-news.com/article/1",
+                "100% fresh",
+                "news",
+            )],
+            vec![],
+        )
+        .await
+        .unwrap();
+
+    // pages follow each other through the cursors. contents of the same time are ordered by id
+    let filter = ContentFilter::source(source_id);
+    let mut titles = vec![];
+    let mut cursor = None;
+    let mut pages = 0;
+    loop {
+        let page = data_store
+            .query_contents(filter.clone(), cursor, 2)
+            .await
+            .unwrap();
+        pages += 1;
+        titles.extend(page.items.into_iter().map(|content| content.title));
+        cursor = match page.next_cursor {
+            Some(next_cursor) => Some(next_cursor.to_string().parse().unwrap()),
+            None => break,
+        };
+    }
+    assert_eq!(pages, 3);
+    assert_eq!(
+        titles,
+        vec!["title 1", "title 2", "title 3", "title 4", "title 5"]
+    );
+    assert_eq!(data_store.count_contents(filter).await.unwrap(), 5);
+
+    // the newest comes first
+    let page = data_store
+        .query_contents(ContentFilter::default(), None, 10)
+        .await
+        .unwrap();
+    assert_eq!(page.items.len(), 6);
+    assert_eq!(page.items[0].title, "100% fresh");
+    assert_eq!(page.next_cursor, None);
+
+    let count = |filter: ContentFilter| data_store.count_contents(filter);
+    let since = ContentFilter {
+        since: Some(middle),
+        ..ContentFilter::default()
+    };
+    assert_eq!(count(since).await.unwrap(), 1);
+    let until = ContentFilter {
+        until: Some(middle),
+        ..ContentFilter::default()
+    };
+    assert_eq!(count(until).await.unwrap(), 5);
+    let keyword = |keyword: &str| ContentFilter {
+        keyword: Some(keyword.to_string()),
+        ..ContentFilter::default()
+    };
+    assert_eq!(count(keyword("TITLE 3")).await.unwrap(), 1);
+    assert_eq!(count(keyword("body")).await.unwrap(), 5);
+    assert_eq!(count(keyword("%")).await.unwrap(), 1);
+    assert_eq!(count(keyword("_")).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn memory_data_store_search_test() {
+    let data_store = MemoryDataStore::new();
+    let source_id = data_store
+        .add_source(sitemap(
+            "formula1-data",
+            "https://formula1-data.com/article",
+        ))
+        .await
+        .unwrap();
+    let long_body = format!("{} Hamilton {}", "a".repeat(100), "b".repeat(100));
+    data_store
+        .commit_job_result(
+            source_id,
+            vec![
+                content(
+                    source_id,
+                    "https://formula1-data.com/article/1",
+                    "Hamilton wins",
+                    "Hamilton won.",
+                ),
+                content(
+                    source_id,
+                    "https://formula1-data.com/article/2",
+                    "Pole",
+                    &long_body,
+                ),
+                content(
+                    source_id,
+                    "https://formula1-data.com/article/3",
+                    "Pit stop",
+                    "slow",
+                ),
+            ],
+            vec![],
+        )
+        .await
+        .unwrap();
+
+    let hits = data_store
+        .search("HAMILTON".to_string(), ContentFilter::default(), 10, 0)
+        .await
+        .unwrap();
+    assert_eq!(hits.len(), 2);
+    assert_eq!(hits[0].content.url, "https://formula1-data.com/article/1");
+    assert_eq!(hits[0].title, "<mark>Hamilton</mark> wins");
+    assert_eq!(hits[0].snippet, "<mark>Hamilton</mark> won.");
+    // a long body is cut around the first match
+    assert!(hits[1].snippet.starts_with('…'));
+    assert!(hits[1].snippet.ends_with('…'));
+    assert!(hits[1].snippet.contains("<mark>Hamilton</mark>"));
+
+    let page = data_store
+        .search("hamilton".to_string(), ContentFilter::default(), 1, 1)
+        .await
+        .unwrap();
+    assert_eq!(page.len(), 1);
+    assert_eq!(page[0].content.url, "https://formula1-data.com/article/2");
+    assert!(data_store
+        .search("hamilton pit".to_string(), ContentFilter::default(), 10, 0)
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(data_store
+        .search("  ".to_string(), ContentFilter::default(), 10, 0)
+        .await
+        .is_err());
+}
@@ -1,23 +1,102 @@
 use crate::crawler::SelectorTree;
+use crate::dispatcher::Schedule;
+use anyhow::{anyhow, Error, Result};
 use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::str::FromStr;
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Source {
     pub id: i32,
     pub name: String,
     pub url: String,
     pub selectors: SelectorTree,
     pub last_accessed: DateTime<Utc>,
-    pub last_accessed_urls: Vec<String>,
     pub created_at: DateTime<Utc>,
+    pub schedule: Schedule,
+    // falseならscheduleに関わらずcrawlしない
+    pub enabled: bool,
+    pub next_run: DateTime<Utc>,
+    // 連続して失敗したjobの回数。成功すると0に戻る
+    pub consecutive_failures: i32,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Content {
     pub id: String,
     pub url: String,
     pub source_id: i32,
     pub title: String,
     pub body: String,
+    // title, body, url以外のfield
+    pub extra: Map<String, Value>,
+    pub published_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
 }
+
+// 全文検索で見つかったcontent
+#[derive(Clone, Debug, Serialize)]
+pub struct SearchHit {
+    pub content: Content,
+    // queryとの関連度。大きいほどよく合う
+    pub score: f64,
+    // queryに合った部分を<mark></mark>で囲んだtitle
+    pub title: String,
+    // bodyのうちqueryに合った部分の前後
+    pub snippet: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ContentRevision {
+    pub id: i32,
+    pub content_id: String,
+    pub source_id: i32,
+    pub url: String,
+    // 変更前のtitle, body
+    pub title: String,
+    pub body: String,
+    pub diff: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Succeeded,
+    Failed,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Succeeded => "succeeded",
+            JobStatus::Failed => "failed",
+        }
+    }
+}
+
+impl FromStr for JobStatus {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "succeeded" => Ok(JobStatus::Succeeded),
+            "failed" => Ok(JobStatus::Failed),
+            _ => Err(anyhow!("unknown job status: {}", s)),
+        }
+    }
+}
+
+// 1回のjobの実行結果
+#[derive(Clone, Debug, Serialize)]
+pub struct JobRun {
+    pub source_id: i32,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub status: JobStatus,
+    pub pages_fetched: i32,
+    pub contents_inserted: i32,
+    // statusがFailedのときのエラー
+    pub error: Option<String>,
+}
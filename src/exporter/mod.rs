@@ -0,0 +1,246 @@
+#[cfg(test)]
+mod test;
+
+use crate::crawler::{Record, Value};
+use crate::entity::Content;
+use anyhow::{anyhow, Error, Result};
+use parquet::basic::{ConvertedType, Repetition, Type as PhysicalType};
+use parquet::data_type::{ByteArray, ByteArrayType};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::types::Type;
+use serde_json;
+use std::io::{BufWriter, Write};
+use std::str::FromStr;
+use std::sync::Arc;
+
+// writes records to a file one by one, so that all rows need not be kept in memory
+pub trait Exporter {
+    fn write(&mut self, record: &Record) -> Result<()>;
+    // flushes buffered rows. must be called after the last write()
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ExportFormat {
+    Csv,
+    JsonLines,
+    Parquet,
+}
+
+impl FromStr for ExportFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "csv" => Ok(ExportFormat::Csv),
+            "jsonl" => Ok(ExportFormat::JsonLines),
+            "parquet" => Ok(ExportFormat::Parquet),
+            _ => Err(anyhow!("unknown export format: {}", s)),
+        }
+    }
+}
+
+// columns of the contents stored by the dispatcher. extra is written as a JSON object
+pub fn content_columns() -> Vec<String> {
+    [
+        "id",
+        "source_id",
+        "url",
+        "title",
+        "body",
+        "published_at",
+        "created_at",
+        "extra",
+    ]
+    .iter()
+    .map(|column| column.to_string())
+    .collect()
+}
+
+// records of content_columns()
+pub fn content_records(contents: &[Content]) -> Vec<Record> {
+    let columns = Arc::new(content_columns());
+    contents
+        .iter()
+        .map(|content| {
+            let values = vec![
+                Some(Value::Text(content.id.clone())),
+                Some(Value::Integer(content.source_id as i64)),
+                Some(Value::Url(content.url.clone())),
+                Some(Value::Text(content.title.clone())),
+                Some(Value::Text(content.body.clone())),
+                content
+                    .published_at
+                    .map(|published_at| Value::DateTime(published_at.fixed_offset())),
+                Some(Value::DateTime(content.created_at.fixed_offset())),
+                Some(Value::Text(
+                    serde_json::Value::Object(content.extra.clone()).to_string(),
+                )),
+            ];
+            Record::new(
+                Arc::clone(&columns),
+                values
+                    .into_iter()
+                    .map(|value| value.map(Arc::new))
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+pub fn new_exporter<W: 'static + Write + Send>(
+    format: ExportFormat,
+    columns: &[String],
+    writer: W,
+) -> Result<Box<dyn Exporter>> {
+    Ok(match format {
+        ExportFormat::Csv => Box::new(CsvExporter::new(columns, writer)?),
+        ExportFormat::JsonLines => Box::new(JsonLinesExporter::new(writer)),
+        ExportFormat::Parquet => Box::new(ParquetExporter::new(columns, writer)?),
+    })
+}
+
+// header row is the columns. a missing value is an empty field
+pub struct CsvExporter<W: Write> {
+    writer: csv::Writer<W>,
+}
+
+impl<W: Write> CsvExporter<W> {
+    pub fn new(columns: &[String], writer: W) -> Result<Self> {
+        let mut writer = csv::Writer::from_writer(writer);
+        writer.write_record(columns)?;
+        Ok(CsvExporter { writer })
+    }
+}
+
+impl<W: Write> Exporter for CsvExporter<W> {
+    fn write(&mut self, record: &Record) -> Result<()> {
+        self.writer.write_record(
+            record
+                .values()
+                .iter()
+                .map(|value| value.as_ref().map(|v| v.to_string()).unwrap_or_default()),
+        )?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+// one JSON object per line
+pub struct JsonLinesExporter<W: Write> {
+    writer: BufWriter<W>,
+}
+
+impl<W: Write> JsonLinesExporter<W> {
+    pub fn new(writer: W) -> Self {
+        JsonLinesExporter {
+            writer: BufWriter::new(writer),
+        }
+    }
+}
+
+impl<W: Write> Exporter for JsonLinesExporter<W> {
+    fn write(&mut self, record: &Record) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, record)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+const ROW_GROUP_SIZE: usize = 1024;
+
+// every column is an optional UTF8 string. rows are buffered and written every ROW_GROUP_SIZE rows
+pub struct ParquetExporter<W: Write + Send> {
+    writer: SerializedFileWriter<W>,
+    // values of each column
+    buffer: Vec<Vec<Option<String>>>,
+    buffered_rows: usize,
+}
+
+impl<W: Write + Send> ParquetExporter<W> {
+    pub fn new(columns: &[String], writer: W) -> Result<Self> {
+        let fields = columns
+            .iter()
+            .map(|column| {
+                Ok(Arc::new(
+                    Type::primitive_type_builder(column, PhysicalType::BYTE_ARRAY)
+                        .with_repetition(Repetition::OPTIONAL)
+                        .with_converted_type(ConvertedType::UTF8)
+                        .build()?,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let schema = Type::group_type_builder("schema")
+            .with_fields(fields)
+            .build()?;
+        let writer = SerializedFileWriter::new(
+            writer,
+            Arc::new(schema),
+            Arc::new(WriterProperties::builder().build()),
+        )?;
+
+        Ok(ParquetExporter {
+            writer,
+            buffer: vec![vec![]; columns.len()],
+            buffered_rows: 0,
+        })
+    }
+
+    fn flush_row_group(&mut self) -> Result<()> {
+        if self.buffered_rows == 0 {
+            return Ok(());
+        }
+
+        let mut row_group = self.writer.next_row_group()?;
+        for column in &mut self.buffer {
+            let mut column_writer = row_group
+                .next_column()?
+                .ok_or_else(|| anyhow!("parquet schema has fewer columns than the buffer"))?;
+            let definition_levels = column
+                .iter()
+                .map(|value| value.is_some() as i16)
+                .collect::<Vec<i16>>();
+            let values = column
+                .drain(..)
+                .flatten()
+                .map(|value| ByteArray::from(value.into_bytes()))
+                .collect::<Vec<ByteArray>>();
+            column_writer.typed::<ByteArrayType>().write_batch(
+                &values,
+                Some(&definition_levels),
+                None,
+            )?;
+            column_writer.close()?;
+        }
+        row_group.close()?;
+        self.buffered_rows = 0;
+        Ok(())
+    }
+}
+
+impl<W: Write + Send> Exporter for ParquetExporter<W> {
+    fn write(&mut self, record: &Record) -> Result<()> {
+        for (column, value) in self.buffer.iter_mut().zip(record.values()) {
+            column.push(value.as_ref().map(|v| v.to_string()));
+        }
+        self.buffered_rows += 1;
+        if self.buffered_rows >= ROW_GROUP_SIZE {
+            self.flush_row_group()?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.flush_row_group()?;
+        self.writer.close()?;
+        Ok(())
+    }
+}
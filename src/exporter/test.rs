@@ -0,0 +1,119 @@
+use crate::crawler::{Record, Value};
+use crate::entity::Content;
+use crate::exporter::{
+    content_columns, content_records, CsvExporter, Exporter, JsonLinesExporter, ParquetExporter,
+};
+use chrono::{TimeZone, Utc};
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use serde_json::{json, Map};
+use std::env;
+use std::fs::File;
+use std::sync::Arc;
+
+fn records() -> Vec<Record> {
+    let columns = Arc::new(vec!["title".to_string(), "laps".to_string()]);
+    vec![
+        Record::new(
+            Arc::clone(&columns),
+            vec![
+                Some(Arc::new("title, \"A\"".into())),
+                Some(Arc::new(Value::Integer(16845))),
+            ],
+        ),
+        Record::new(
+            Arc::clone(&columns),
+            vec![Some(Arc::new("title B".into())), None],
+        ),
+    ]
+}
+
+fn export(mut exporter: Box<dyn Exporter + '_>) {
+    for record in records() {
+        exporter.write(&record).unwrap();
+    }
+    exporter.finish().unwrap();
+}
+
+#[test]
+fn csv_exporter_test() {
+    let mut buf = vec![];
+    export(Box::new(
+        CsvExporter::new(&["title".into(), "laps".into()], &mut buf).unwrap(),
+    ));
+
+    assert_eq!(
+        "title,laps\n\"title, \"\"A\"\"\",16845\ntitle B,\n",
+        String::from_utf8(buf).unwrap()
+    )
+}
+
+#[test]
+fn json_lines_exporter_test() {
+    let mut buf = vec![];
+    export(Box::new(JsonLinesExporter::new(&mut buf)));
+
+    assert_eq!(
+        "{\"title\":\"title, \\\"A\\\"\",\"laps\":16845}\n{\"title\":\"title B\",\"laps\":null}\n",
+        String::from_utf8(buf).unwrap()
+    )
+}
+
+#[test]
+fn parquet_exporter_test() {
+    let path = env::temp_dir().join(format!(
+        "scrapper-exporter-test-{}.parquet",
+        std::process::id()
+    ));
+    export(Box::new(
+        ParquetExporter::new(
+            &["title".into(), "laps".into()],
+            File::create(&path).unwrap(),
+        )
+        .unwrap(),
+    ));
+
+    let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+    let rows = reader
+        .get_row_iter(None)
+        .unwrap()
+        .map(|row| row.unwrap().to_string())
+        .collect::<Vec<String>>();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(
+        vec![
+            "{title: \"title, \"A\"\", laps: \"16845\"}",
+            "{title: \"title B\", laps: null}",
+        ],
+        rows
+    )
+}
+
+#[test]
+fn content_records_test() {
+    let mut extra = Map::new();
+    extra.insert("laps".to_string(), json!(53));
+    let content = Content {
+        id: "8f14e45f".to_string(),
+        url: "https://formula1-data.com/article/1".to_string(),
+        source_id: 1,
+        title: "title".to_string(),
+        body: "body".to_string(),
+        extra,
+        published_at: None,
+        created_at: Utc.with_ymd_and_hms(2020, 8, 1, 0, 0, 0).unwrap(),
+    };
+
+    let mut buf = vec![];
+    let mut exporter = Box::new(CsvExporter::new(&content_columns(), &mut buf).unwrap());
+    for record in content_records(&[content]) {
+        exporter.write(&record).unwrap();
+    }
+    exporter.finish().unwrap();
+
+    assert_eq!(
+        "id,source_id,url,title,body,published_at,created_at,extra\n\
+         8f14e45f,1,https://formula1-data.com/article/1,title,body,,2020-08-01T00:00:00+00:00,\"{\"\"laps\"\":53}\"\n",
+        String::from_utf8(buf).unwrap()
+    );
+}
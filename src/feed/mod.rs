@@ -0,0 +1,118 @@
+#[cfg(test)]
+mod test;
+
+use crate::entity::{Content, Source};
+use anyhow::{anyhow, Error, Result};
+use atom_syndication::{EntryBuilder, FeedBuilder, LinkBuilder, Text};
+use chrono::{DateTime, Utc};
+use rss::{ChannelBuilder, GuidBuilder, ItemBuilder};
+use std::str::FromStr;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FeedFormat {
+    Rss,
+    Atom,
+}
+
+impl FromStr for FeedFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "rss" => Ok(FeedFormat::Rss),
+            "atom" => Ok(FeedFormat::Atom),
+            _ => Err(anyhow!("unknown feed format: {}", s)),
+        }
+    }
+}
+
+// contents are expected to be ordered from the latest
+pub fn render(format: FeedFormat, source: &Source, contents: &[Content]) -> String {
+    match format {
+        FeedFormat::Rss => to_rss(source, contents),
+        FeedFormat::Atom => to_atom(source, contents),
+    }
+}
+
+// names are neither unique nor restricted, so the file is named by the id
+// followed by the name with anything but [A-Za-z0-9_-] replaced
+pub fn filename(format: FeedFormat, source: &Source) -> String {
+    let extension = match format {
+        FeedFormat::Rss => "rss",
+        FeedFormat::Atom => "atom",
+    };
+    let slug = source
+        .name
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("_");
+    if slug.is_empty() {
+        format!("{}.{}", source.id, extension)
+    } else {
+        format!("{}-{}.{}", source.id, slug, extension)
+    }
+}
+
+pub fn to_rss(source: &Source, contents: &[Content]) -> String {
+    let items = contents
+        .iter()
+        .map(|content| {
+            ItemBuilder::default()
+                .title(content.title.clone())
+                .link(content.url.clone())
+                .description(content.body.clone())
+                .guid(
+                    GuidBuilder::default()
+                        .value(content.url.clone())
+                        .permalink(true)
+                        .build(),
+                )
+                .pub_date(published(content).to_rfc2822())
+                .build()
+        })
+        .collect::<Vec<_>>();
+
+    ChannelBuilder::default()
+        .title(source.name.clone())
+        .link(source.url.clone())
+        .description(format!("contents crawled from {}", source.url))
+        .last_build_date(updated(contents).map(|dt| dt.to_rfc2822()))
+        .items(items)
+        .build()
+        .to_string()
+}
+
+pub fn to_atom(source: &Source, contents: &[Content]) -> String {
+    let entries = contents
+        .iter()
+        .map(|content| {
+            EntryBuilder::default()
+                .title(content.title.clone())
+                .id(content.url.clone())
+                .link(LinkBuilder::default().href(content.url.clone()).build())
+                .summary(Some(Text::plain(content.body.clone())))
+                .updated(published(content))
+                .published(Some(published(content).into()))
+                .build()
+        })
+        .collect::<Vec<_>>();
+
+    FeedBuilder::default()
+        .title(source.name.clone())
+        .id(source.url.clone())
+        .link(LinkBuilder::default().href(source.url.clone()).build())
+        .updated(updated(contents).unwrap_or(source.created_at))
+        .entries(entries)
+        .build()
+        .to_string()
+}
+
+// contents without published_at are dated when they were crawled
+fn published(content: &Content) -> DateTime<Utc> {
+    content.published_at.unwrap_or(content.created_at)
+}
+
+fn updated(contents: &[Content]) -> Option<DateTime<Utc>> {
+    contents.iter().map(published).max()
+}
@@ -0,0 +1,122 @@
+use crate::crawler::SelectorTree;
+use crate::dispatcher::Schedule;
+use crate::entity::{Content, Source};
+use crate::feed::{filename, to_atom, to_rss, FeedFormat};
+use chrono::{TimeZone, Utc};
+use serde_json::Map;
+use std::str::FromStr;
+
+fn source() -> Source {
+    Source {
+        id: 1,
+        name: "formula1-data".to_string(),
+        url: "https://formula1-data.com/article".to_string(),
+        selectors: SelectorTree::new(
+            r#"{"_id": "formula1-data", "startUrl": ["https://formula1-data.com/article"], "selectors": []}"#
+                .to_string(),
+        )
+        .unwrap(),
+        last_accessed: Utc.with_ymd_and_hms(2020, 8, 2, 0, 0, 0).unwrap(),
+        created_at: Utc.with_ymd_and_hms(2020, 8, 1, 0, 0, 0).unwrap(),
+        schedule: Schedule::default(),
+        enabled: true,
+        next_run: Utc.with_ymd_and_hms(2020, 8, 2, 1, 0, 0).unwrap(),
+        consecutive_failures: 0,
+    }
+}
+
+fn contents() -> Vec<Content> {
+    vec![
+        Content {
+            id: "b".to_string(),
+            url: "https://formula1-data.com/article/b".to_string(),
+            source_id: 1,
+            title: "title B".to_string(),
+            body: "body <B>".to_string(),
+            extra: Map::new(),
+            published_at: Some(Utc.with_ymd_and_hms(2020, 8, 3, 12, 0, 0).unwrap()),
+            created_at: Utc.with_ymd_and_hms(2020, 8, 4, 0, 0, 0).unwrap(),
+        },
+        Content {
+            id: "a".to_string(),
+            url: "https://formula1-data.com/article/a".to_string(),
+            source_id: 1,
+            title: "title A".to_string(),
+            body: "body A".to_string(),
+            extra: Map::new(),
+            published_at: None,
+            created_at: Utc.with_ymd_and_hms(2020, 8, 2, 0, 0, 0).unwrap(),
+        },
+    ]
+}
+
+#[test]
+fn to_rss_test() {
+    let channel = rss::Channel::from_str(&to_rss(&source(), &contents())).unwrap();
+
+    assert_eq!(channel.title(), "formula1-data");
+    assert_eq!(channel.link(), "https://formula1-data.com/article");
+    assert_eq!(
+        channel.last_build_date(),
+        Some("Mon, 3 Aug 2020 12:00:00 +0000")
+    );
+
+    let items = channel.items();
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0].title(), Some("title B"));
+    assert_eq!(items[0].link(), Some("https://formula1-data.com/article/b"));
+    assert_eq!(items[0].description(), Some("body <B>"));
+    assert_eq!(items[0].pub_date(), Some("Mon, 3 Aug 2020 12:00:00 +0000"));
+    // created_at is used when published_at is unknown
+    assert_eq!(items[1].pub_date(), Some("Sun, 2 Aug 2020 00:00:00 +0000"));
+}
+
+#[test]
+fn to_atom_test() {
+    let feed = atom_syndication::Feed::from_str(&to_atom(&source(), &contents())).unwrap();
+
+    assert_eq!(feed.title().as_str(), "formula1-data");
+    assert_eq!(feed.id(), "https://formula1-data.com/article");
+    assert_eq!(feed.updated().to_rfc3339(), "2020-08-03T12:00:00+00:00");
+
+    let entries = feed.entries();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].title().as_str(), "title B");
+    assert_eq!(
+        entries[0].links()[0].href(),
+        "https://formula1-data.com/article/b"
+    );
+    assert_eq!(entries[0].summary().unwrap().as_str(), "body <B>");
+    assert_eq!(
+        entries[1].updated().to_rfc3339(),
+        "2020-08-02T00:00:00+00:00"
+    );
+
+    // a source without contents is dated when it was created
+    let empty = atom_syndication::Feed::from_str(&to_atom(&source(), &[])).unwrap();
+    assert_eq!(empty.updated().to_rfc3339(), "2020-08-01T00:00:00+00:00");
+}
+
+#[test]
+fn filename_test() {
+    let named = |id: i32, name: &str| Source {
+        id,
+        name: name.to_string(),
+        ..source()
+    };
+    assert_eq!(
+        filename(FeedFormat::Rss, &named(1, "formula1-data")),
+        "1-formula1-data.rss"
+    );
+    // sources of the same name get other files
+    assert_eq!(
+        filename(FeedFormat::Rss, &named(2, "formula1-data")),
+        "2-formula1-data.rss"
+    );
+    assert_eq!(
+        filename(FeedFormat::Atom, &named(3, "../F1 data/2020")),
+        "3-F1_data_2020.atom"
+    );
+    assert_eq!(filename(FeedFormat::Atom, &named(4, "")), "4.atom");
+    assert_eq!(filename(FeedFormat::Rss, &named(5, "日本語")), "5.rss");
+}
@@ -1,5 +1,8 @@
 #[macro_use]
 extern crate diesel;
+pub mod api;
 pub mod crawler;
 pub mod dispatcher;
 pub mod entity;
+pub mod exporter;
+pub mod feed;